@@ -0,0 +1,214 @@
+//! Pinhole or orthographic camera producing primary rays.
+
+use std::f64;
+
+#[cfg(test)]
+use crate::sampling;
+use crate::{matrix::Matrix4x4, vec2::Vec2, vec3::Vec3, vec4::Vec4, Ray};
+
+/// The way the scene is projected onto the viewport.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+    /// Rays start at the camera position and diverge according to `fov`.
+    Perspective,
+    /// Rays share the view direction and start on the viewport of
+    /// `ortho_height` extent, keeping sizes independent of the distance.
+    Orthographic,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub position: Vec3<f64>,
+    /// Point the camera looks at.
+    pub target: Vec3<f64>,
+    /// Approximate up direction, doesn't have to be orthogonal to the view
+    /// direction.
+    pub up: Vec3<f64>,
+    /// Vertical field of view in degrees, perspective projection only.
+    pub fov: f64,
+    pub projection: Projection,
+    /// Height of the viewport in world units, orthographic projection only.
+    pub ortho_height: f64,
+    /// Diameter of the lens, `0.0` keeps everything in focus.
+    pub aperture: f64,
+    /// Distance along the view direction to the plane that stays sharp.
+    pub focus_distance: f64,
+    /// Resolution of the rendered image in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Number of jittered rays averaged per pixel for anti-aliasing.
+    pub samples: u32,
+}
+
+impl Camera {
+    pub fn new(position: Vec3<f64>, target: Vec3<f64>, up: Vec3<f64>, fov: f64) -> Self {
+        Self {
+            position,
+            target,
+            up,
+            fov,
+            ..Self::default()
+        }
+    }
+
+    /// Moves the camera without changing its direction.
+    pub fn translate(&mut self, offset: Vec3<f64>) {
+        self.position = self.position + offset;
+        self.target = self.target + offset;
+    }
+
+    /// Returns the ray passing through the center of the given pixel of a
+    /// `width` x `height` image.
+    pub fn ray_for(&self, px: u32, py: u32, width: u32, height: u32) -> Ray<f64> {
+        self.ray(px as f64 + 0.5, py as f64 + 0.5, width, height)
+    }
+
+    /// Returns the ray passing through the given point of a `width` x `height`
+    /// image, where `(0, 0)` is the top left corner.
+    pub fn ray(&self, x: f64, y: f64, width: u32, height: u32) -> Ray<f64> {
+        self.lens_ray(x, y, width, height, Vec2::default())
+    }
+
+    /// Returns the ray through the given point of the image, starting at the
+    /// given point of the lens within the unit disk.
+    ///
+    /// Rays through the same image point meet on the focal plane, so the
+    /// farther objects are from it the more they blur.
+    pub fn lens_ray(&self, x: f64, y: f64, width: u32, height: u32, lens: Vec2<f64>) -> Ray<f64> {
+        // Rows of the view matrix are the camera axes in world coordinates.
+        let to_world = Matrix4x4::look_at(self.position, self.target, self.up).transpose();
+        let axis = |axis: Vec3<f64>| -> Vec3<f64> { (&to_world * Vec4::direction(axis)).into() };
+        let u = axis(Vec3::new(1.0, 0.0, 0.0));
+        let v = axis(Vec3::new(0.0, 1.0, 0.0));
+        let w = axis(Vec3::new(0.0, 0.0, 1.0));
+
+        // Half of the viewport extent, at the unit distance from the camera
+        // for the perspective projection.
+        let half_height = match self.projection {
+            Projection::Perspective => (self.fov.to_radians() / 2.0).tan(),
+            Projection::Orthographic => self.ortho_height / 2.0,
+        };
+        let half_width = half_height * width as f64 / height as f64;
+
+        let sx = (2.0 * x / width as f64 - 1.0) * half_width;
+        let sy = (1.0 - 2.0 * y / height as f64) * half_height;
+
+        let (origin, direction, range) = match self.projection {
+            Projection::Perspective => (self.position, w + u.scale(sx) + v.scale(sy), 1.0..1.0e20),
+            Projection::Orthographic => (self.position + u.scale(sx) + v.scale(sy), w, 0.0..1.0e20),
+        };
+
+        if self.aperture <= 0.0 {
+            return Ray::new(origin, direction, range);
+        }
+
+        // The direction is one unit long along the view axis in both
+        // projections, so this is the point on the focal plane.
+        let focus = origin + direction.scale(self.focus_distance);
+        let radius = self.aperture / 2.0;
+        let origin = origin + u.scale(lens.x * radius) + v.scale(lens.y * radius);
+
+        Ray::new(origin, focus - origin, range)
+    }
+}
+
+impl Default for Camera {
+    /// Perspective camera at `(0, 0, -2)` looking along the z axis, with the
+    /// viewport of unit height at the unit distance, 800x800 resolution, a
+    /// single sample per pixel and everything in focus.
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 0.0, -2.0),
+            target: Vec3::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            fov: 2.0 * 0.5f64.atan().to_degrees(),
+            projection: Projection::Perspective,
+            ortho_height: 1.0,
+            aperture: 0.0,
+            focus_distance: 1.0,
+            width: 800,
+            height: 800,
+            samples: 1,
+        }
+    }
+}
+
+#[test]
+fn center_ray_is_forward() {
+    let camera = Camera::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.0, 2.0, 10.0), Vec3::new(0.0, 1.0, 0.0), 60.0);
+    let ray = camera.ray_for(50, 50, 101, 101);
+
+    assert_eq!(Vec3::new(1.0, 2.0, 3.0), ray.origin());
+    assert_eq!(Vec3::new(0.0, 0.0, 1.0), *ray.direction());
+}
+
+#[test]
+fn corner_rays_widen_with_fov() {
+    let forward = Vec3::new(0.0, 0.0, 1.0);
+    let corner = |fov| {
+        let camera = Camera::new(Vec3::default(), forward, Vec3::new(0.0, 1.0, 0.0), fov);
+        camera.ray(0.0, 0.0, 100, 100).direction().dot(&forward)
+    };
+
+    assert!(corner(90.0) < corner(45.0));
+    assert!(corner(45.0) < corner(10.0));
+}
+
+#[test]
+fn jittered_rays_stay_within_pixel() {
+    let camera = Camera::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 60.0);
+    // Projects the ray direction onto the viewport at the unit distance.
+    let project = |x: f64, y: f64| {
+        let direction = *camera.ray(x, y, 16, 16).direction();
+        (direction.x / direction.z, direction.y / direction.z)
+    };
+
+    let (left, top) = project(5.0, 9.0);
+    let (right, bottom) = project(6.0, 10.0);
+
+    for frame in 0..64 {
+        let offset = sampling::subpixel_offset(frame);
+        let (x, y) = project(5.0 + offset.x, 9.0 + offset.y);
+
+        assert!(left <= x && x < right);
+        assert!(bottom < y && y <= top);
+    }
+}
+
+#[test]
+fn orthographic_rays_are_parallel() {
+    let camera = Camera {
+        projection: Projection::Orthographic,
+        ortho_height: 4.0,
+        ..Camera::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 60.0)
+    };
+
+    for &(x, y) in &[(0.0, 0.0), (3.0, 7.0), (8.0, 4.0)] {
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), *camera.ray(x, y, 8, 4).direction());
+    }
+
+    // The 2:1 image spans the 8x4 viewport centered at the camera position.
+    assert_eq!(Vec3::new(-4.0, 2.0, 0.0), camera.ray(0.0, 0.0, 8, 4).origin());
+    assert_eq!(Vec3::new(4.0, -2.0, 0.0), camera.ray(8.0, 4.0, 8, 4).origin());
+}
+
+#[test]
+fn lens_rays_converge_on_focal_plane() {
+    let camera = Camera {
+        aperture: 0.5,
+        focus_distance: 4.0,
+        ..Camera::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 60.0)
+    };
+    // Intersects the ray with the focal plane `z = 4`.
+    let focus = |ray: &Ray<f64>| ray.offset((4.0 - ray.origin().z) / ray.direction().z);
+
+    let center = camera.ray(3.0, 7.0, 16, 16);
+    for &(lx, ly) in &[(1.0, 0.0), (0.0, -1.0), (-0.5, 0.5)] {
+        let ray = camera.lens_ray(3.0, 7.0, 16, 16, Vec2::new(lx, ly));
+
+        assert_ne!(center.origin(), ray.origin());
+        assert!(focus(&center).distance(&focus(&ray)) < 1e-9);
+    }
+}
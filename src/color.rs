@@ -0,0 +1,153 @@
+//! Output color encoding.
+
+use image::Rgb;
+
+/// Linear sRGB to linear Display P3 primaries conversion, row-major.
+const SRGB_TO_DISPLAY_P3: [[f64; 3]; 3] = [
+    [0.822_462_1, 0.177_538_0, 0.000_000_0],
+    [0.033_194_2, 0.966_805_8, 0.000_000_0],
+    [0.017_082_7, 0.072_397_4, 0.910_519_9],
+];
+
+/// Color space rendered images are encoded in.
+///
+/// Traced colors are linear with sRGB primaries, which the default `Srgb`
+/// space keeps while applying the sRGB transfer function.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+    DisplayP3,
+    Rec709,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::Srgb
+    }
+}
+
+impl ColorSpace {
+    /// Converts a linear color with sRGB primaries into the linear color with
+    /// the primaries of this color space.
+    pub fn primaries(&self, linear: [f64; 3]) -> [f64; 3] {
+        match *self {
+            ColorSpace::Srgb | ColorSpace::Linear | ColorSpace::Rec709 => linear,
+            ColorSpace::DisplayP3 => {
+                let m = SRGB_TO_DISPLAY_P3;
+                [
+                    m[0][0] * linear[0] + m[0][1] * linear[1] + m[0][2] * linear[2],
+                    m[1][0] * linear[0] + m[1][1] * linear[1] + m[1][2] * linear[2],
+                    m[2][0] * linear[0] + m[2][1] * linear[1] + m[2][2] * linear[2],
+                ]
+            }
+        }
+    }
+
+    /// Applies the transfer function of this color space to a linear channel
+    /// in `[0, 1]`.
+    pub fn transfer(&self, linear: f64) -> f64 {
+        match *self {
+            // Display P3 shares the sRGB transfer function.
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => linear_to_srgb(linear),
+            ColorSpace::Linear => linear,
+            ColorSpace::Rec709 => linear_to_rec709(linear),
+        }
+    }
+
+    /// Encodes a linear color with sRGB primaries and components in `[0, 1]`.
+    pub fn encode(&self, linear: [f64; 3]) -> Rgb<u8> {
+        let primaries = self.primaries(linear);
+        let encode = |c: f64| (self.transfer(c.max(0.0).min(1.0)) * 255.0).round() as u8;
+
+        Rgb([encode(primaries[0]), encode(primaries[1]), encode(primaries[2])])
+    }
+}
+
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Mapping of unbounded light intensities into the displayable range.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapping {
+    /// Cuts everything brighter than `1.0` off, blowing highlights out.
+    Clamp,
+    /// Reinhard operator `c / (1 + c)`, compressing highlights smoothly while
+    /// keeping dark colors almost intact.
+    Reinhard,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping::Clamp
+    }
+}
+
+impl ToneMapping {
+    /// Maps a non-negative linear channel into `[0, 1]`.
+    pub fn apply(&self, linear: f64) -> f64 {
+        let linear = linear.max(0.0);
+
+        match *self {
+            ToneMapping::Clamp => linear.min(1.0),
+            ToneMapping::Reinhard => linear / (1.0 + linear),
+        }
+    }
+}
+
+/// Clamps the channel into `[0, 1]`, applies the gamma and quantizes it.
+///
+/// A gamma of `1.0` leaves the channel as is.
+pub fn encode_channel(linear: f64, gamma: f64) -> u8 {
+    (linear.max(0.0).min(1.0).powf(1.0 / gamma) * 255.0).round() as u8
+}
+
+fn linear_to_rec709(c: f64) -> f64 {
+    if c < 0.018 {
+        c * 4.5
+    } else {
+        1.099 * c.powf(0.45) - 0.099
+    }
+}
+
+#[test]
+fn encode_midtone() {
+    assert_eq!(Rgb([188, 188, 188]), ColorSpace::Srgb.encode([0.5, 0.5, 0.5]));
+    assert_eq!(Rgb([128, 128, 128]), ColorSpace::Linear.encode([0.5, 0.5, 0.5]));
+    assert_eq!(Rgb([180, 180, 180]), ColorSpace::Rec709.encode([0.5, 0.5, 0.5]));
+}
+
+#[test]
+fn encode_primaries() {
+    assert_eq!(Rgb([255, 0, 0]), ColorSpace::Srgb.encode([1.0, 0.0, 0.0]));
+    // Pure sRGB red lies inside the wider P3 gamut.
+    assert_eq!(Rgb([234, 51, 35]), ColorSpace::DisplayP3.encode([1.0, 0.0, 0.0]));
+}
+
+#[test]
+fn encode_channel_with_gamma() {
+    assert_eq!(0, encode_channel(0.0, 2.2));
+    assert_eq!(255, encode_channel(1.0, 2.2));
+    assert_eq!(186, encode_channel(0.5, 2.2));
+    assert_eq!(128, encode_channel(0.5, 1.0));
+    // Out of range channels are clamped.
+    assert_eq!(0, encode_channel(-0.5, 2.2));
+    assert_eq!(255, encode_channel(1.5, 2.2));
+}
+
+#[test]
+fn reinhard_compresses_highlights() {
+    assert_eq!(1.0, ToneMapping::Clamp.apply(4.0));
+    assert_eq!(ToneMapping::Clamp.apply(4.0), ToneMapping::Clamp.apply(8.0));
+
+    assert_eq!(0.8, ToneMapping::Reinhard.apply(4.0));
+    assert!(ToneMapping::Reinhard.apply(4.0) < ToneMapping::Reinhard.apply(8.0));
+    assert!(ToneMapping::Reinhard.apply(1.0e6) < 1.0);
+}
@@ -0,0 +1,126 @@
+//! Axis-aligned bounding box.
+
+use std::f64;
+
+use crate::{vec3::Vec3, Ray};
+
+/// Axis-aligned box used to quickly reject rays that can't hit the geometry
+/// inside of it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3<f64>,
+    pub max: Vec3<f64>,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3<f64>, max: Vec3<f64>) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns a box that contains nothing, i.e. the identity for `union`.
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    /// Returns the smallest box enclosing all the given points.
+    pub fn from_points(points: &[Vec3<f64>]) -> Self {
+        points.iter().fold(Self::empty(), |aabb, &point| aabb.union(&Self::new(point, point)))
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec3<f64> {
+        (self.min + self.max).scale(0.5)
+    }
+
+    /// Returns the smallest box enclosing both boxes.
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Vec3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Vec3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+
+    /// Checks whether the ray passes through the box within its range.
+    #[inline]
+    pub fn intersects(&self, ray: &Ray<f64>) -> bool {
+        self.interval(ray).is_some()
+    }
+
+    /// Returns the `(t0, t1)` interval the ray spends inside the box, clipped
+    /// to the ray range.
+    pub fn interval(&self, ray: &Ray<f64>) -> Option<(f64, f64)> {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let range = ray.range();
+
+        let slabs = [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+            (origin.z, direction.z, self.min.z, self.max.z),
+        ];
+
+        let mut t0 = range.start;
+        let mut t1 = range.end;
+
+        for &(o, d, min, max) in &slabs {
+            let inv = 1.0 / d;
+            let (near, far) = if inv < 0.0 {
+                ((max - o) * inv, (min - o) * inv)
+            } else {
+                ((min - o) * inv, (max - o) * inv)
+            };
+
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t0 > t1 {
+                return None;
+            }
+        }
+
+        Some((t0, t1))
+    }
+}
+
+#[cfg(test)]
+fn unit_box() -> Aabb {
+    Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0))
+}
+
+#[test]
+fn intersects_hit() {
+    let ray = Ray::new(Vec3::new(0.5, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0..f64::INFINITY);
+
+    assert!(unit_box().intersects(&ray));
+}
+
+#[test]
+fn intersects_miss() {
+    let ray = Ray::new(Vec3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0..f64::INFINITY);
+
+    assert!(!unit_box().intersects(&ray));
+}
+
+#[test]
+fn intersects_from_inside() {
+    let ray = Ray::new(Vec3::default(), Vec3::new(1.0, 1.0, 0.0).unit(), 0.0..f64::INFINITY);
+
+    assert!(unit_box().intersects(&ray));
+}
+
+#[test]
+fn intersects_out_of_range() {
+    let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0..2.0);
+
+    assert!(!unit_box().intersects(&ray));
+}
+
+#[test]
+fn union_encloses_both() {
+    let a = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+    let b = Aabb::new(Vec3::new(-1.0, 0.5, 0.5), Vec3::new(0.5, 2.0, 0.5));
+
+    assert_eq!(Aabb::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 1.0)), a.union(&b));
+}
@@ -0,0 +1,185 @@
+//! Bounding volume hierarchy over mesh triangles and scene models.
+
+use std::{cmp::Ordering, f64};
+
+use crate::{
+    geometry::{Aabb, Culling, Triangle},
+    Intersection, Ray,
+};
+
+/// Maximum number of primitives stored in a single leaf.
+const LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// Primitives referenced by `indices[start..end]`.
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        end: usize,
+    },
+    Branch {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    #[inline]
+    fn bounds(&self) -> &Aabb {
+        match *self {
+            Node::Leaf { ref bounds, .. } | Node::Branch { ref bounds, .. } => bounds,
+        }
+    }
+}
+
+///
+/// Binary tree of nested bounding boxes, which allows to test only the
+/// primitives whose boxes are pierced by the ray.
+///
+/// The hierarchy stores indices into the primitive slice it was built from, so
+/// it must be rebuilt whenever primitives change.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    /// Nodes in depth-first order, the root is the first one.
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(triangles: &[Triangle<f64>]) -> Self {
+        let bounds: Vec<Aabb> = triangles.iter().map(|triangle| triangle.bounds()).collect();
+
+        Self::from_bounds(&bounds)
+    }
+
+    /// Builds the hierarchy over primitives with the given bounding boxes.
+    pub fn from_bounds(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: (0..bounds.len()).collect(),
+        };
+
+        bvh.build(bounds, 0, bounds.len());
+
+        bvh
+    }
+
+    #[inline]
+    pub fn bounds(&self) -> Aabb {
+        *self.nodes[0].bounds()
+    }
+
+    /// Builds the subtree over `indices[start..end]`, splitting the primitives
+    /// at the median centroid along the longest axis, and returns its index.
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize) -> usize {
+        let node_bounds = self.indices[start..end].iter().fold(Aabb::empty(), |aabb, &idx| aabb.union(&bounds[idx]));
+
+        let id = self.nodes.len();
+        if end - start <= LEAF_SIZE {
+            self.nodes.push(Node::Leaf {
+                bounds: node_bounds,
+                start,
+                end,
+            });
+            return id;
+        }
+
+        let centroids = self.indices[start..end].iter().fold(Aabb::empty(), |aabb, &idx| {
+            let center = bounds[idx].center();
+            aabb.union(&Aabb::new(center, center))
+        });
+
+        let extent = centroids.max - centroids.min;
+        let axis = |aabb: &Aabb| {
+            let center = aabb.center();
+            if extent.x > extent.y && extent.x > extent.z {
+                center.x
+            } else if extent.y > extent.z {
+                center.y
+            } else {
+                center.z
+            }
+        };
+
+        self.indices[start..end].sort_by(|&a, &b| axis(&bounds[a]).partial_cmp(&axis(&bounds[b])).unwrap_or(Ordering::Equal));
+
+        // Reserve the slot, because children are pushed after the parent.
+        self.nodes.push(Node::Leaf {
+            bounds: node_bounds,
+            start,
+            end,
+        });
+
+        let mid = start + (end - start) / 2;
+        let left = self.build(bounds, start, mid);
+        let right = self.build(bounds, mid, end);
+        self.nodes[id] = Node::Branch {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+
+        id
+    }
+
+    /// Returns the nearest hit among the given triangles, which must be the
+    /// same ones the hierarchy was built from.
+    pub fn intersection(&self, triangles: &[Triangle<f64>], ray: &Ray<f64>, culling: Culling) -> Option<Intersection> {
+        let mut closest: Option<Intersection> = None;
+
+        self.traverse(ray, |idx| {
+            let intersection = triangles[idx].intersection_culled(ray, culling)?;
+            if closest.map_or(true, |closest| intersection.t < closest.t) {
+                closest = Some(intersection.with_primitive(idx as u32));
+            }
+
+            Some(intersection.t)
+        });
+
+        closest
+    }
+
+    /// Visits the indices of primitives whose boxes are pierced by the ray.
+    ///
+    /// The visitor returns the distance of the hit with the primitive, if any,
+    /// so that boxes lying entirely behind the closest hit are skipped.
+    /// Returning negative infinity skips everything left, which ends the
+    /// traversal.
+    pub fn traverse<F>(&self, ray: &Ray<f64>, mut visit: F)
+    where
+        F: FnMut(usize) -> Option<f64>,
+    {
+        let mut closest = f64::INFINITY;
+        let mut stack = vec![0];
+
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+
+            // Skip boxes that are missed or lie entirely behind the closest
+            // hit found so far.
+            match node.bounds().interval(ray) {
+                Some((t0, ..)) if t0 <= closest => {}
+                Some(..) | None => continue,
+            }
+
+            match *node {
+                Node::Leaf { start, end, .. } => {
+                    for &idx in &self.indices[start..end] {
+                        if let Some(t) = visit(idx) {
+                            closest = closest.min(t);
+                        }
+                        if closest == f64::NEG_INFINITY {
+                            return;
+                        }
+                    }
+                }
+                Node::Branch { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Planes cutting away part of a model, e.g. for cutaway views.
+
+use crate::{matrix::Matrix4x4, transform::Transform, vec3::Vec3, vec4::Vec4, Material, Ray};
+
+/// Keeps the half-space the normal points to and hides everything behind it.
+///
+/// When the plane cuts through a solid and a cap material is set, the cut is
+/// closed with a flat surface lying in the plane.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ClipPlane {
+    point: Vec3<f64>,
+    normal: Vec3<f64>,
+    #[serde(default)]
+    cap: Option<Material>,
+}
+
+impl ClipPlane {
+    pub fn new(point: Vec3<f64>, normal: Vec3<f64>) -> Self {
+        Self { point, normal, cap: None }
+    }
+
+    pub fn with_cap(mut self, material: Material) -> Self {
+        self.cap = Some(material);
+        self
+    }
+
+    #[inline]
+    pub fn normal(&self) -> Vec3<f64> {
+        self.normal
+    }
+
+    #[inline]
+    pub fn cap(&self) -> Option<&Material> {
+        self.cap.as_ref()
+    }
+
+    /// Checks whether the given point is cut away.
+    #[inline]
+    pub fn clips(&self, point: &Vec3<f64>) -> bool {
+        (*point - self.point).dot(&self.normal) < 0.0
+    }
+
+    /// Returns the `t` at which the ray enters the visible half-space, if it
+    /// does.
+    pub fn entry(&self, ray: &Ray<f64>) -> Option<f64> {
+        let denominator = self.normal.dot(ray.direction());
+        if denominator <= 0.0 {
+            return None;
+        }
+
+        Some((self.point - ray.origin()).dot(&self.normal) / denominator)
+    }
+}
+
+/// Moves the plane with the model it clips, so that it cuts the model in the
+/// same place wherever the model is placed.
+impl Transform<f64> for ClipPlane {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        self.point = (transformation * Vec4::from(self.point)).into();
+        let normal: Vec3<f64> = (&transformation.normal_matrix() * Vec4::direction(self.normal)).into();
+        self.normal = normal.unit();
+    }
+}
@@ -0,0 +1,153 @@
+use std::f64;
+
+use crate::{
+    geometry::{Aabb, Geometry},
+    matrix::Matrix4x4,
+    transform::Transform,
+    vec3::Vec3,
+    vec4::Vec4,
+    Intersection, Ray,
+};
+
+/// Axis-aligned box.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct Cuboid {
+    min: Vec3<f64>,
+    max: Vec3<f64>,
+}
+
+impl Cuboid {
+    pub fn new(min: Vec3<f64>, max: Vec3<f64>) -> Self {
+        Self { min, max }
+    }
+}
+
+/// Returns the unit vector along the given axis pointing to the `sign` side.
+fn axis_normal(axis: usize, sign: f64) -> Vec3<f64> {
+    match axis {
+        0 => Vec3::new(sign, 0.0, 0.0),
+        1 => Vec3::new(0.0, sign, 0.0),
+        _ => Vec3::new(0.0, 0.0, sign),
+    }
+}
+
+impl Geometry for Cuboid {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        let origin = ray.origin();
+        let direction = ray.direction();
+
+        let slabs = [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+            (origin.z, direction.z, self.min.z, self.max.z),
+        ];
+
+        // Entry and exit distances along with the axes of the faces crossed.
+        let (mut t0, mut t0_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t1, mut t1_axis) = (f64::INFINITY, 0);
+
+        for (axis, &(o, d, min, max)) in slabs.iter().enumerate() {
+            let inv = 1.0 / d;
+            let (near, far) = if inv < 0.0 {
+                ((max - o) * inv, (min - o) * inv)
+            } else {
+                ((min - o) * inv, (max - o) * inv)
+            };
+
+            if near > t0 {
+                t0 = near;
+                t0_axis = axis;
+            }
+            if far < t1 {
+                t1 = far;
+                t1_axis = axis;
+            }
+            if t0 > t1 {
+                return None;
+            }
+        }
+
+        let sign = |axis: usize| if slabs[axis].1 < 0.0 { -1.0 } else { 1.0 };
+
+        // Rays starting inside the box leave it through the far face.
+        let (t, normal) = if ray.contains(t0) {
+            (t0, axis_normal(t0_axis, -sign(t0_axis)))
+        } else if ray.contains(t1) {
+            (t1, axis_normal(t1_axis, sign(t1_axis)))
+        } else {
+            return None;
+        };
+
+        Some(Intersection::new(t, ray.offset(t), normal))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+/// Transforms the corners and takes their bounds, so rotated boxes grow to
+/// the axis-aligned box enclosing them rather than being rotated.
+impl Transform<f64> for Cuboid {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+        for idx in 0..8 {
+            let corner = Vec3::new(
+                if idx & 1 == 0 { self.min.x } else { self.max.x },
+                if idx & 2 == 0 { self.min.y } else { self.max.y },
+                if idx & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            let corner: Vec3<f64> = (transformation * Vec4::from(corner)).into();
+
+            min = Vec3::new(min.x.min(corner.x), min.y.min(corner.y), min.z.min(corner.z));
+            max = Vec3::new(max.x.max(corner.x), max.y.max(corner.y), max.z.max(corner.z));
+        }
+
+        self.min = min;
+        self.max = max;
+    }
+}
+
+#[test]
+fn intersection_with_each_face() {
+    let cuboid = Cuboid::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let faces = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(-1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+    ];
+
+    for normal in &faces {
+        // Slightly off-center, so that the ray doesn't pass through edges.
+        let offset = Vec3::new(0.1, 0.2, 0.3);
+        let origin = normal.scale(5.0) + offset - normal.scale(normal.dot(&offset));
+        let ray = Ray::new(origin, normal.inverse(), 0.0..1.0e20);
+
+        let intersection = cuboid.intersection(&ray).unwrap();
+        assert_eq!(*normal, intersection.normal);
+        assert!((intersection.t - 4.0).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn intersection_from_inside() {
+    let cuboid = Cuboid::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0), 0.0..1.0e20);
+
+    let intersection = cuboid.intersection(&ray).unwrap();
+    assert_eq!(1.0, intersection.t);
+    assert_eq!(Vec3::new(0.0, 1.0, 0.0), intersection.normal);
+}
+
+#[test]
+fn intersection_miss() {
+    let cuboid = Cuboid::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let ray = Ray::new(Vec3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    assert!(cuboid.intersection(&ray).is_none());
+}
@@ -0,0 +1,126 @@
+use crate::{
+    geometry::{circle_bounds, radius_scale, Aabb, Geometry},
+    matrix::Matrix4x4,
+    transform::Transform,
+    vec3::Vec3,
+    vec4::Vec4,
+    Intersection, Ray,
+};
+
+/// Lateral surface of a finite cylinder, without caps.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct Cylinder {
+    /// Center of the bottom circle.
+    base: Vec3<f64>,
+    /// Direction from the bottom circle to the top one.
+    axis: Vec3<f64>,
+    radius: f64,
+    height: f64,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3<f64>, axis: Vec3<f64>, radius: f64, height: f64) -> Self {
+        Self { base, axis, radius, height }
+    }
+}
+
+impl Geometry for Cylinder {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        let axis = self.axis.unit();
+        let oc = ray.origin() - self.base;
+
+        // Solve for the infinite cylinder by projecting everything onto the
+        // plane perpendicular to the axis.
+        let d = *ray.direction() - axis.scale(ray.direction().dot(&axis));
+        let o = oc - axis.scale(oc.dot(&axis));
+
+        let a = d.dot(&d);
+        if a < 1e-12 {
+            // Rays parallel to the axis never cross the lateral surface.
+            return None;
+        }
+
+        let b = 2.0 * o.dot(&d);
+        let c = o.dot(&o) - self.radius.powi(2);
+
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrt = discriminant.sqrt();
+        let near = (-b - sqrt) / (2.0 * a);
+        let far = (-b + sqrt) / (2.0 * a);
+
+        for &t in &[near, far] {
+            if !ray.contains(t) {
+                continue;
+            }
+
+            let point = ray.offset(t);
+            let h = (point - self.base).dot(&axis);
+            if h < 0.0 || h > self.height {
+                continue;
+            }
+
+            let normal = (point - self.base - axis.scale(h)).unit();
+            return Some(Intersection::new(t, point, normal));
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let axis = self.axis.unit();
+        let bottom = circle_bounds(self.base, axis, self.radius);
+        let top = circle_bounds(self.base + axis.scale(self.height), axis, self.radius);
+
+        Some(bottom.union(&top))
+    }
+}
+
+impl Transform<f64> for Cylinder {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        let axis: Vec3<f64> = (transformation * Vec4::direction(self.axis.unit())).into();
+
+        self.base = (transformation * Vec4::from(self.base)).into();
+        self.axis = axis;
+        self.height *= axis.len();
+        self.radius *= radius_scale(transformation);
+    }
+}
+
+#[cfg(test)]
+fn y_cylinder() -> Cylinder {
+    Cylinder::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 1.0, 0.0), 1.0, 2.0)
+}
+
+#[test]
+fn intersection_with_side() {
+    let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    let intersection = y_cylinder().intersection(&ray).unwrap();
+    assert_eq!(4.0, intersection.t);
+    assert_eq!(Vec3::new(0.0, 0.0, -1.0), intersection.normal);
+}
+
+#[test]
+fn intersection_above_top() {
+    let ray = Ray::new(Vec3::new(0.0, 2.5, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    assert!(y_cylinder().intersection(&ray).is_none());
+}
+
+#[test]
+fn intersection_along_axis() {
+    let ray = Ray::new(Vec3::new(0.0, -5.0, 5.0), Vec3::new(0.0, 1.0, 0.0), 0.0..1.0e20);
+
+    assert!(y_cylinder().intersection(&ray).is_none());
+}
+
+#[test]
+fn bounding_box() {
+    let aabb = y_cylinder().bounding_box().unwrap();
+
+    assert_eq!(Aabb::new(Vec3::new(-1.0, 0.0, 4.0), Vec3::new(1.0, 2.0, 6.0)), aabb);
+}
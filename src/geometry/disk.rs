@@ -0,0 +1,92 @@
+use crate::{
+    geometry::{circle_bounds, radius_scale, Aabb, Geometry},
+    matrix::Matrix4x4,
+    transform::Transform,
+    vec3::Vec3,
+    vec4::Vec4,
+    Intersection, Ray,
+};
+
+/// Flat round surface, i.e. a plane bounded by a circle.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct Disk {
+    center: Vec3<f64>,
+    normal: Vec3<f64>,
+    radius: f64,
+}
+
+impl Disk {
+    pub fn new(center: Vec3<f64>, normal: Vec3<f64>, radius: f64) -> Self {
+        Self { center, normal, radius }
+    }
+}
+
+impl Geometry for Disk {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        let denominator = self.normal.dot(ray.direction());
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+
+        let t = (self.center - ray.origin()).dot(&self.normal) / denominator;
+        if !ray.contains(t) {
+            return None;
+        }
+
+        let point = ray.offset(t);
+        if point.distance_squared(&self.center) > self.radius * self.radius {
+            return None;
+        }
+
+        Some(Intersection::new(t, point, self.normal))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(circle_bounds(self.center, self.normal.unit(), self.radius))
+    }
+}
+
+impl Transform<f64> for Disk {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        self.center = (transformation * Vec4::from(self.center)).into();
+        let normal: Vec3<f64> = (&transformation.normal_matrix() * Vec4::direction(self.normal)).into();
+        self.normal = normal.unit();
+        self.radius *= radius_scale(transformation);
+    }
+}
+
+#[cfg(test)]
+fn xy_disk() -> Disk {
+    Disk::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 1.0)
+}
+
+#[test]
+fn intersection_at_center() {
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    let intersection = xy_disk().intersection(&ray).unwrap();
+    assert_eq!(5.0, intersection.t);
+    assert_eq!(Vec3::new(0.0, 0.0, 5.0), intersection.point);
+    assert_eq!(Vec3::new(0.0, 0.0, -1.0), intersection.normal);
+}
+
+#[test]
+fn intersection_outside_radius() {
+    let ray = Ray::new(Vec3::new(0.0, 1.001, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    assert!(xy_disk().intersection(&ray).is_none());
+}
+
+#[test]
+fn intersection_parallel() {
+    let ray = Ray::new(Vec3::new(-5.0, 0.0, 5.0), Vec3::new(1.0, 0.0, 0.0), 0.0..1.0e20);
+
+    assert!(xy_disk().intersection(&ray).is_none());
+}
+
+#[test]
+fn bounding_box() {
+    let aabb = xy_disk().bounding_box().unwrap();
+
+    assert_eq!(Aabb::new(Vec3::new(-1.0, -1.0, 5.0), Vec3::new(1.0, 1.0, 5.0)), aabb);
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use crate::{
+    geometry::{Aabb, Culling, Geometry, Mesh},
+    matrix::Matrix4x4,
+    transform::Transform,
+    vec3::Vec3,
+    vec4::Vec4,
+    Intersection, Ray,
+};
+
+/// Mesh shared between several models, each placed by its own transformation.
+///
+/// Transformations apply to the instance only, the shared triangles stay in
+/// the object space. Rays are transformed into the object space instead.
+#[derive(Clone, Debug)]
+pub struct MeshInstance {
+    mesh: Arc<Mesh>,
+    culling: Culling,
+    transform: Matrix4x4<f64>,
+    inverse: Matrix4x4<f64>,
+    normal_matrix: Matrix4x4<f64>,
+}
+
+impl MeshInstance {
+    pub fn new(mesh: Arc<Mesh>) -> Self {
+        Self {
+            mesh,
+            culling: Culling::default(),
+            transform: Matrix4x4::identity(),
+            inverse: Matrix4x4::identity(),
+            normal_matrix: Matrix4x4::identity(),
+        }
+    }
+
+    pub fn with_culling(mut self, culling: Culling) -> Self {
+        self.culling = culling;
+        self
+    }
+}
+
+impl Geometry for MeshInstance {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        let origin: Vec3<f64> = (&self.inverse * Vec4::from(ray.origin())).into();
+        let direction: Vec3<f64> = (&self.inverse * Vec4::direction(*ray.direction())).into();
+
+        // The object space ray is normalized, so distances along it are
+        // scaled compared to the world space ones.
+        let scale = direction.len();
+        let range = ray.range();
+        let object_ray = Ray::new(origin, direction, range.start * scale..range.end * scale);
+
+        self.mesh.intersection_culled(&object_ray, self.culling).map(|mut intersection| {
+            let normal: Vec3<f64> = (&self.normal_matrix * Vec4::direction(intersection.normal)).into();
+
+            intersection.t /= scale;
+            intersection.point = ray.offset(intersection.t);
+            intersection.normal = normal.unit();
+            intersection
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.mesh.bounds();
+        let corners: Vec<Vec3<f64>> = (0..8)
+            .map(|idx| {
+                let corner = Vec3::new(
+                    if idx & 1 == 0 { bounds.min.x } else { bounds.max.x },
+                    if idx & 2 == 0 { bounds.min.y } else { bounds.max.y },
+                    if idx & 4 == 0 { bounds.min.z } else { bounds.max.z },
+                );
+                (&self.transform * Vec4::from(corner)).into()
+            })
+            .collect();
+
+        Some(Aabb::from_points(&corners))
+    }
+}
+
+impl Transform<f64> for MeshInstance {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        self.transform = *transformation * self.transform;
+        self.inverse = self.transform.inverse();
+        self.normal_matrix = self.inverse.transpose();
+    }
+}
+
+#[test]
+fn instance_matches_transformed_mesh() {
+    use crate::geometry::cube;
+
+    let transformation =
+        Matrix4x4::translation(Vec3::new(1.0, 2.0, 10.0)) * Matrix4x4::rotation_y(0.5) * Matrix4x4::scaling(Vec3::new(2.0, 1.0, 1.0));
+
+    let mut mesh = cube(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let mut instance = MeshInstance::new(Arc::new(mesh.clone()));
+    mesh.transform(&transformation);
+    instance.transform(&transformation);
+
+    for &(x, y) in &[(1.0, 2.0), (0.5, 2.5), (2.5, 1.5), (-0.5, 2.0)] {
+        let ray = Ray::new(Vec3::new(x, y, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+        let expected = mesh.intersection(&ray).unwrap();
+        let actual = instance.intersection(&ray).unwrap();
+
+        assert!((expected.t - actual.t).abs() < 1e-9);
+        assert!(expected.point.distance(&actual.point) < 1e-9);
+        assert!(expected.normal.unit().distance(&actual.normal) < 1e-9);
+        assert_eq!(expected.primitive, actual.primitive);
+    }
+}
+
+#[test]
+fn instance_respects_ray_range() {
+    use crate::geometry::cube;
+
+    let mut instance = MeshInstance::new(Arc::new(cube(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0))));
+    instance.transform(&(Matrix4x4::translation(Vec3::new(0.0, 0.0, 10.0)) * Matrix4x4::scaling(Vec3::new(3.0, 3.0, 3.0))));
+
+    let ray = |end| Ray::new(Vec3::new(0.3, 0.7, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..end);
+
+    assert!((instance.intersection(&ray(7.5)).unwrap().t - 7.0).abs() < 1e-9);
+    assert!(instance.intersection(&ray(6.5)).is_none());
+}
+
+#[test]
+fn bounding_box_follows_transform() {
+    use crate::geometry::cube;
+
+    let mut instance = MeshInstance::new(Arc::new(cube(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0))));
+    instance.transform(&Matrix4x4::translation(Vec3::new(2.0, 0.0, 0.0)));
+
+    assert_eq!(Some(Aabb::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0))), instance.bounding_box());
+}
@@ -1,16 +1,45 @@
 //! Model that contains one or more triangles.
 
 use std::{
+    collections::HashMap,
     error::Error,
     f64,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
     path::Path,
 };
 
-use crate::{geometry::Geometry, matrix::Matrix4x4, transform::Transform, vec3::Vec3, vec4::Vec4, Intersection, Ray};
+use rayon::prelude::*;
 
-#[derive(Copy, Clone, Debug)]
+use crate::{
+    geometry::{Aabb, Bvh, Geometry},
+    matrix::Matrix4x4,
+    transform::Transform,
+    vec2::Vec2,
+    vec3::Vec3,
+    vec4::Vec4,
+    Intersection, Ray,
+};
+
+/// Which triangle faces can be hit by rays.
+///
+/// A face is front-facing when its vertices appear in counter-clockwise order
+/// as seen by the ray.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Culling {
+    TwoSided,
+    CullBackface,
+    CullFrontface,
+}
+
+impl Default for Culling {
+    fn default() -> Self {
+        Culling::TwoSided
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Triangle<T> {
     ///
     vertices: [Vec3<T>; 3],
@@ -19,15 +48,19 @@ pub struct Triangle<T> {
     /// All the same if our triangle is *flat*.
     /// Values differ when we want interpolation. e.g. round things like teapot.
     normals: [Vec3<T>; 3],
+
+    /// Texture coordinates at vertices.
+    uvs: [Vec2<T>; 3],
 }
 
 impl Triangle<f64> {
     pub fn new(vertices: [Vec3<f64>; 3]) -> Self {
-        let n = vertices[0].cross(&vertices[1]).unit();
+        let n = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0])).unit();
 
         Self {
             vertices,
             normals: [n, n, n],
+            uvs: [Vec2::default(); 3],
         }
     }
 
@@ -35,17 +68,44 @@ impl Triangle<f64> {
         self.normals = normals;
         self
     }
+
+    pub fn with_uvs(mut self, uvs: [Vec2<f64>; 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    #[inline]
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.vertices)
+    }
+
+    #[inline]
+    pub fn centroid(&self) -> Vec3<f64> {
+        (self.vertices[0] + self.vertices[1] + self.vertices[2]).scale(1.0 / 3.0)
+    }
+
+    #[inline]
+    pub fn area(&self) -> f64 {
+        (self.vertices[1] - self.vertices[0]).cross(&(self.vertices[2] - self.vertices[0])).len() / 2.0
+    }
 }
 
-impl Geometry for Triangle<f64> {
-    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+impl Triangle<f64> {
+    pub fn intersection_culled(&self, ray: &Ray<f64>, culling: Culling) -> Option<Intersection> {
         let e1 = self.vertices[1] - self.vertices[0];
         let e2 = self.vertices[2] - self.vertices[0];
         let p = ray.direction().cross(&e2);
         let determinant = e1.dot(&p);
 
         // If determinant is near zero, ray lies in the plane of triangle.
-        if determinant.abs() < f64::EPSILON {
+        // Otherwise its sign tells whether the ray hits the front face.
+        let culled = match culling {
+            Culling::TwoSided => determinant.abs() < f64::EPSILON,
+            Culling::CullBackface => determinant < f64::EPSILON,
+            Culling::CullFrontface => determinant > -f64::EPSILON,
+        };
+
+        if culled {
             return None;
         }
 
@@ -69,133 +129,956 @@ impl Geometry for Triangle<f64> {
 
             // Interpolate normals at vertices to get normal
             let n = self.normals[0].scale(alpha) + self.normals[1].scale(beta) + self.normals[2].scale(gamma);
+            let uv = Vec2::barycentric(&self.uvs, alpha, beta, gamma);
 
-            Some(Intersection {
-                t,
-                normal: n,
-                point: ray.offset(t),
-            })
+            Some(Intersection::new(t, ray.offset(t), n).with_uv(uv))
         } else {
             None
         }
     }
 }
 
+impl Geometry for Triangle<f64> {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        self.intersection_culled(ray, Culling::TwoSided)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds())
+    }
+}
+
+impl Triangle<f64> {
+    /// Transforms the triangle given the precomputed normal matrix, so that
+    /// meshes invert the transformation once rather than per triangle.
+    fn transform_with(&mut self, transformation: &Matrix4x4<f64>, normal_matrix: &Matrix4x4<f64>) {
+        for vertex in &mut self.vertices {
+            *vertex = (transformation * Vec4::from(*vertex)).into();
+        }
+        for normal in &mut self.normals {
+            *normal = (normal_matrix * Vec4::direction(*normal)).into();
+        }
+    }
+}
+
 impl Transform<f64> for Triangle<f64> {
     fn transform(&mut self, transformation: &Matrix4x4<f64>) {
-        self.vertices[0] = (transformation * Vec4::from(self.vertices[0])).into();
-        self.vertices[1] = (transformation * Vec4::from(self.vertices[1])).into();
-        self.vertices[2] = (transformation * Vec4::from(self.vertices[2])).into();
-
-        let inverse = transformation.inverse();
-        self.normals[0] = Matrix4x4::transform_normal(&self.normals[0], inverse);
-        self.normals[1] = Matrix4x4::transform_normal(&self.normals[1], inverse);
-        self.normals[2] = Matrix4x4::transform_normal(&self.normals[2], inverse);
+        self.transform_with(transformation, &transformation.normal_matrix());
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Mesh {
     pub triangles: Vec<Triangle<f64>>,
+    /// Hierarchy over the triangles, must be rebuilt whenever vertices move.
+    bvh: Bvh,
 }
 
 impl Mesh {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
-        let file = File::open(path)?;
-        let file = BufReader::new(file);
+    pub fn new(triangles: Vec<Triangle<f64>>) -> Self {
+        let bvh = Bvh::new(&triangles);
 
-        let mut vertices: Vec<Vec3<f64>> = Vec::new();
-        let mut normals: Vec<Vec3<f64>> = Vec::new();
-        let mut triangles = Vec::new();
+        Self { triangles, bvh }
+    }
 
-        for line in file.lines() {
-            let line = line?;
-            let tokens: Vec<&str> = line[..].split_whitespace().collect();
-            if tokens.is_empty() {
+    #[inline]
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    /// Tests every triangle, used to verify the hierarchy.
+    #[cfg(test)]
+    fn intersection_linear(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        let mut t = f64::INFINITY;
+        let mut closest = None;
+
+        for (idx, triangle) in self.triangles.iter().enumerate() {
+            if let Some(intersection) = triangle.intersection_culled(ray, Culling::TwoSided) {
+                if intersection.t < t && ray.contains(intersection.t) {
+                    t = intersection.t;
+                    closest = Some(intersection.with_primitive(idx as u32));
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Replaces the normals with the ones averaged over the triangles sharing
+    /// each vertex, so that the surface looks smooth rather than faceted.
+    ///
+    /// Vertices are shared when their positions are equal. Face normals are
+    /// weighted by the triangle angle at the vertex, which makes the result
+    /// independent of how polygons were split into triangles.
+    ///
+    /// Degenerate triangles have no normal and are left out of the averages.
+    pub fn with_smooth_normals(mut self) -> Self {
+        let key = |v: &Vec3<f64>| (v.x.to_bits(), v.y.to_bits(), v.z.to_bits());
+
+        let mut sums: HashMap<_, Vec3<f64>> = HashMap::new();
+        for triangle in &self.triangles {
+            let v = &triangle.vertices;
+            let (e1, e2) = (v[1] - v[0], v[2] - v[0]);
+            let cross = e1.cross(&e2);
+            if cross.len() <= f64::EPSILON * e1.len() * e2.len() {
                 continue;
             }
+            let n = cross.unit();
 
-            println!("{:?}", tokens.get(0));
-            match tokens.get(0) {
-                // Vertexes.
-                Some(&"v") => match (tokens.get(1), tokens.get(2), tokens.get(3)) {
-                    (Some(x), Some(y), Some(z)) => {
-                        vertices.push(Vec3::new(x.parse()?, y.parse()?, z.parse()?));
-                    }
-                    (..) => return Err("invalid `v` token".into()),
-                },
-                Some(&"vn") => match (tokens.get(1), tokens.get(2), tokens.get(3)) {
-                    (Some(x), Some(y), Some(z)) => {
-                        normals.push(Vec3::new(x.parse()?, y.parse()?, z.parse()?));
-                    }
-                    (..) => return Err("invalid `vn` token".into()),
-                },
-                // Faces
-                Some(&"f") => {
-                    let tail = match tokens.split_first() {
-                        Some((.., tail)) => tail,
-                        None => {
-                            return Err("face syntax of `obj` not supported or malformed".into());
-                        }
-                    };
+            for i in 0..3 {
+                let a = (v[(i + 1) % 3] - v[i]).unit();
+                let b = (v[(i + 2) % 3] - v[i]).unit();
+                let angle = a.dot(&b).max(-1.0).min(1.0).acos();
 
-                    let pairs: Vec<Vec<usize>> = tail
-                        .iter()
-                        .map(|token| {
-                            let str_tokens: Vec<&str> = token.split('/').collect();
-                            str_tokens
-                                .iter()
-                                .map(|str_tok| {
-                                    match str_tok.parse::<usize>().ok() {
-                                        Some(usize_tok) => usize_tok - 1, // Have to offset as OBJ is 1-indexed
-                                        None => !0,                       // No data available/not supplied (eg. `//` as a token)
-                                    }
-                                })
-                                .collect()
-                        })
-                        .collect();
-
-                    triangles.push(
-                        Triangle::new([vertices[pairs[0][0]], vertices[pairs[1][0]], vertices[pairs[2][0]]]).with_normals([
-                            normals[pairs[0][2]],
-                            normals[pairs[1][2]],
-                            normals[pairs[2][2]],
-                        ]),
-                    );
+                let sum = sums.entry(key(&v[i])).or_insert_with(Vec3::default);
+                *sum = *sum + n.scale(angle);
+            }
+        }
+
+        for triangle in &mut self.triangles {
+            for i in 0..3 {
+                if let Some(sum) = sums.get(&key(&triangle.vertices[i])) {
+                    triangle.normals[i] = sum.unit();
                 }
-                Some(..) => {}
-                None => {}
             }
         }
 
-        let mesh = Self { triangles };
+        self
+    }
 
-        Ok(mesh)
+    /// Finds the closest hit, culling triangles as given.
+    ///
+    /// Meshes are two-sided on their own, culling is configured per instance,
+    /// see `MeshInstance::with_culling`.
+    pub fn intersection_culled(&self, ray: &Ray<f64>, culling: Culling) -> Option<Intersection> {
+        self.bvh.intersection(&self.triangles, ray, culling)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        let file = File::open(path)?;
+
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Parses a mesh from a `data:` URI holding a base64 encoded OBJ file.
+    pub fn from_data_uri(uri: &str) -> Result<Self, Box<Error>> {
+        let data = match uri.find(',') {
+            Some(pos) if uri.starts_with("data:") && uri[..pos].ends_with(";base64") => &uri[pos + 1..],
+            Some(..) | None => return Err("only base64 encoded `data:` URIs are supported".into()),
+        };
+
+        let bytes = base64::decode(data)?;
+
+        Self::from_reader(&bytes[..])
+    }
+
+    /// Parses a mesh in the Wavefront OBJ format.
+    ///
+    /// Triangles are built in parallel once the whole file is read and every
+    /// index is checked.
+    pub fn from_reader<R: BufRead>(file: R) -> Result<Self, Box<Error>> {
+        let obj = Obj::parse(file)?;
+        let triangles = obj.faces.par_iter().map(|face| obj.triangle(face)).collect();
+
+        Ok(Self::new(triangles))
+    }
+
+    pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        Self::from_stl(&bytes)
+    }
+
+    /// Parses a mesh in either the ASCII or the binary STL format.
+    ///
+    /// Facet normals are taken from the file, unless they are zero, which
+    /// exporters often write leaving the normal up to the vertex order.
+    pub fn from_stl(bytes: &[u8]) -> Result<Self, Box<Error>> {
+        // Binary files may start with `solid` too, so the size matching the
+        // facet count in the header tells the formats apart.
+        let binary = bytes.len() >= 84 && {
+            let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+            bytes.len() == 84 + 50 * count
+        };
+
+        let facets = if binary {
+            stl_binary_facets(&bytes[84..])
+        } else {
+            stl_ascii_facets(bytes)?
+        };
+
+        let triangles = facets
+            .into_iter()
+            .map(|(normal, vertices)| {
+                let triangle = Triangle::new(vertices);
+                if normal == Vec3::default() {
+                    triangle
+                } else {
+                    let n = normal.unit();
+                    triangle.with_normals([n, n, n])
+                }
+            })
+            .collect();
+
+        Ok(Self::new(triangles))
     }
 }
 
-impl Geometry for Mesh {
-    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
-        let mut t = f64::INFINITY;
-        let mut closest = None;
+/// Facet normal followed by the facet vertices.
+type Facet = (Vec3<f64>, [Vec3<f64>; 3]);
 
-        for triangle in &self.triangles {
-            if let Some(intersection) = triangle.intersection(ray) {
-                if intersection.t < t && ray.contains(intersection.t) {
-                    t = intersection.t;
-                    closest = Some(intersection);
+/// Reads 50 byte records of the binary STL, with the header already skipped.
+fn stl_binary_facets(records: &[u8]) -> Vec<Facet> {
+    let vec3 = |bytes: &[u8]| {
+        let f = |i: usize| f32::from_bits(u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])) as f64;
+        Vec3::new(f(0), f(4), f(8))
+    };
+
+    records
+        .chunks(50)
+        .map(|record| (vec3(&record[0..]), [vec3(&record[12..]), vec3(&record[24..]), vec3(&record[36..])]))
+        .collect()
+}
+
+fn stl_ascii_facets(bytes: &[u8]) -> Result<Vec<Facet>, Box<Error>> {
+    let mut facets = Vec::new();
+    let mut normal = Vec3::default();
+    let mut vertices = Vec::new();
+
+    for (number, line) in bytes.lines().enumerate() {
+        let line = line?;
+        let number = number + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        let vec3 = |tokens: &[&str]| -> Result<Vec3<f64>, Box<Error>> {
+            match tokens {
+                [x, y, z] => Ok(Vec3::new(x.parse()?, y.parse()?, z.parse()?)),
+                _ => Err(format!("line {}: expected 3 coordinates, got {}", number, tokens.len()).into()),
+            }
+        };
+
+        match tokens.get(0) {
+            Some(&"facet") => {
+                normal = match tokens.get(1) {
+                    Some(&"normal") => vec3(&tokens[2..])?,
+                    _ => Vec3::default(),
+                };
+                vertices.clear();
+            }
+            Some(&"vertex") => vertices.push(vec3(&tokens[1..])?),
+            Some(&"endfacet") => match vertices[..] {
+                [a, b, c] => facets.push((normal, [a, b, c])),
+                _ => return Err(format!("line {}: facet must have 3 vertices, got {}", number, vertices.len()).into()),
+            },
+            Some(..) | None => {}
+        }
+    }
+
+    Ok(facets)
+}
+
+/// Vertex, texture coordinate and normal indices of a face vertex, the latter
+/// two are optional.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Contents of an OBJ file, with polygons split into triangles.
+struct Obj {
+    vertices: Vec<Vec3<f64>>,
+    normals: Vec<Vec3<f64>>,
+    uvs: Vec<Vec2<f64>>,
+    /// Faces referring to valid indices only.
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+impl Obj {
+    /// Reads the file line by line, failing at the first malformed line.
+    fn parse<R: BufRead>(file: R) -> Result<Self, Box<Error>> {
+        let mut obj = Self {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+        };
+
+        for (number, line) in file.lines().enumerate() {
+            let line = line?;
+            obj.parse_line(&line).map_err(|err| format!("line {}: {}", number + 1, err))?;
+        }
+
+        Ok(obj)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), Box<Error>> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.get(0) {
+            // Vertexes.
+            Some(&"v") => match (tokens.get(1), tokens.get(2), tokens.get(3)) {
+                (Some(x), Some(y), Some(z)) => {
+                    self.vertices.push(Vec3::new(parse_float(x)?, parse_float(y)?, parse_float(z)?));
+                }
+                (..) => return Err("invalid `v` token".into()),
+            },
+            Some(&"vn") => match (tokens.get(1), tokens.get(2), tokens.get(3)) {
+                (Some(x), Some(y), Some(z)) => {
+                    self.normals.push(Vec3::new(parse_float(x)?, parse_float(y)?, parse_float(z)?));
+                }
+                (..) => return Err("invalid `vn` token".into()),
+            },
+            // Texture coordinates, the optional depth is ignored.
+            Some(&"vt") => match (tokens.get(1), tokens.get(2)) {
+                (Some(u), v) => {
+                    let v = match v {
+                        Some(v) => parse_float(v)?,
+                        None => 0.0,
+                    };
+                    self.uvs.push(Vec2::new(parse_float(u)?, v));
+                }
+                (..) => return Err("invalid `vt` token".into()),
+            },
+            // Faces
+            Some(&"f") => {
+                let mut pairs: Vec<FaceVertex> = Vec::new();
+                for token in &tokens[1..] {
+                    let mut pair = [None; 3];
+                    for (kind, index) in token.split('/').take(3).enumerate() {
+                        if index.is_empty() {
+                            continue;
+                        }
+
+                        let (name, count) = match kind {
+                            0 => ("vertex", self.vertices.len()),
+                            1 => ("texture coordinate", self.uvs.len()),
+                            _ => ("normal", self.normals.len()),
+                        };
+                        pair[kind] = Some(resolve_index(index, count).map_err(|err| format!("{} {}", name, err))?);
+                    }
+
+                    let vertex = pair[0].ok_or_else(|| format!("face vertex `{}` has no vertex index", token))?;
+                    pairs.push((vertex, pair[1], pair[2]));
+                }
+
+                if pairs.len() < 3 {
+                    return Err(format!("face must have at least 3 vertices, got {}", pairs.len()).into());
+                }
+
+                // Polygons are split into a fan of triangles sharing the first vertex.
+                for i in 1..pairs.len() - 1 {
+                    self.faces.push([pairs[0], pairs[i], pairs[i + 1]]);
                 }
             }
+            Some(..) => {}
+            None => {}
         }
 
-        closest
+        Ok(())
+    }
+
+    fn triangle(&self, face: &[FaceVertex; 3]) -> Triangle<f64> {
+        let [a, b, c] = face;
+        let triangle = Triangle::new([self.vertices[a.0], self.vertices[b.0], self.vertices[c.0]]);
+
+        // Faces like `f v` or `f v/vt` carry no normal indices, in which case
+        // the flat normal is used.
+        let normal = |pair: &FaceVertex| pair.2.map(|idx| self.normals[idx]);
+        let triangle = match (normal(a), normal(b), normal(c)) {
+            (Some(na), Some(nb), Some(nc)) => triangle.with_normals([na, nb, nc]),
+            (..) => triangle,
+        };
+
+        let uv = |pair: &FaceVertex| pair.1.map(|idx| self.uvs[idx]);
+        match (uv(a), uv(b), uv(c)) {
+            (Some(ta), Some(tb), Some(tc)) => triangle.with_uvs([ta, tb, tc]),
+            (..) => triangle,
+        }
+    }
+}
+
+/// Parses a single coordinate of a vertex, normal or texture coordinate.
+fn parse_float(token: &str) -> Result<f64, String> {
+    token.parse().map_err(|_| format!("`{}` is not a number", token))
+}
+
+/// Converts the 1-based OBJ index into a 0-based one, given the number of
+/// elements defined so far.
+///
+/// Negative indices are relative to the end, i.e. `-1` is the last element.
+fn resolve_index(index: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = index.parse().map_err(|_| format!("index `{}` is not an integer", index))?;
+
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} is referenced but only {} defined", index, count));
+    }
+
+    Ok(resolved as usize)
+}
+
+impl Geometry for Mesh {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        self.intersection_culled(ray, Culling::TwoSided)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds())
     }
 }
 
 impl Transform<f64> for Mesh {
     fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        let normal_matrix = transformation.normal_matrix();
         for triangle in &mut self.triangles {
-            triangle.transform(transformation);
+            triangle.transform_with(transformation, &normal_matrix);
+        }
+
+        self.bvh = Bvh::new(&self.triangles);
+    }
+}
+
+/// Builds an axis-aligned cube out of triangles with outward normals.
+#[cfg(test)]
+pub fn cube(min: Vec3<f64>, max: Vec3<f64>) -> Mesh {
+    let corner = |i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    };
+
+    let faces = [
+        ([0, 2, 6, 4], Vec3::new(-1.0, 0.0, 0.0)),
+        ([1, 3, 7, 5], Vec3::new(1.0, 0.0, 0.0)),
+        ([0, 1, 5, 4], Vec3::new(0.0, -1.0, 0.0)),
+        ([2, 3, 7, 6], Vec3::new(0.0, 1.0, 0.0)),
+        ([0, 1, 3, 2], Vec3::new(0.0, 0.0, -1.0)),
+        ([4, 5, 7, 6], Vec3::new(0.0, 0.0, 1.0)),
+    ];
+
+    let mut triangles = Vec::new();
+    for &([a, b, c, d], n) in &faces {
+        triangles.push(Triangle::new([corner(a), corner(b), corner(c)]).with_normals([n, n, n]));
+        triangles.push(Triangle::new([corner(a), corner(c), corner(d)]).with_normals([n, n, n]));
+    }
+
+    Mesh::new(triangles)
+}
+
+#[cfg(test)]
+const TWO_TRIANGLES: &str = "
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.0 1.0 0.0
+v 0.0 1.0 0.0
+vn 0.0 0.0 1.0
+f 1//1 2//1 3//1
+f 1//1 3//1 4//1
+";
+
+#[cfg(test)]
+fn xy_triangle() -> Triangle<f64> {
+    // Front face looks towards positive z.
+    Triangle::new([Vec3::new(0.0, 0.0, 5.0), Vec3::new(1.0, 0.0, 5.0), Vec3::new(0.0, 1.0, 5.0)])
+}
+
+#[test]
+fn flat_normal_from_edges() {
+    let n = Vec3::new(0.0, 0.0, 1.0);
+
+    assert_eq!([n, n, n], xy_triangle().normals);
+}
+
+#[test]
+fn area_of_right_triangle() {
+    let triangle = Triangle::new([Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)]);
+
+    assert_eq!(0.5, triangle.area());
+}
+
+#[test]
+fn centroid_of_equilateral_triangle() {
+    let h = 3f64.sqrt() / 2.0;
+    let triangle = Triangle::new([Vec3::new(-0.5, 0.0, 2.0), Vec3::new(0.5, 0.0, 2.0), Vec3::new(0.0, h, 2.0)]);
+
+    assert!(triangle.centroid().distance(&Vec3::new(0.0, h / 3.0, 2.0)) < 1e-12);
+    assert!((triangle.area() - h / 2.0).abs() < 1e-12);
+}
+
+#[test]
+fn culling_modes() {
+    let triangle = xy_triangle();
+    let front = Ray::new(Vec3::new(0.25, 0.25, 10.0), Vec3::new(0.0, 0.0, -1.0), 0.0..f64::INFINITY);
+    let back = Ray::new(Vec3::new(0.25, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..f64::INFINITY);
+
+    assert!(triangle.intersection_culled(&front, Culling::TwoSided).is_some());
+    assert!(triangle.intersection_culled(&back, Culling::TwoSided).is_some());
+
+    assert!(triangle.intersection_culled(&front, Culling::CullBackface).is_some());
+    assert!(triangle.intersection_culled(&back, Culling::CullBackface).is_none());
+
+    assert!(triangle.intersection_culled(&front, Culling::CullFrontface).is_none());
+    assert!(triangle.intersection_culled(&back, Culling::CullFrontface).is_some());
+}
+
+#[test]
+fn deserialize_culling() {
+    assert_eq!(Culling::CullBackface, serde_json::from_str("\"cull_backface\"").unwrap());
+    assert_eq!(Culling::TwoSided, serde_json::from_str("\"two_sided\"").unwrap());
+}
+
+#[test]
+fn load_from_data_uri() {
+    let uri = format!("data:model/obj;base64,{}", base64::encode(TWO_TRIANGLES));
+    let mesh = Mesh::from_data_uri(&uri).unwrap();
+
+    assert_eq!(2, mesh.triangles.len());
+    assert_eq!(
+        [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)],
+        mesh.triangles[1].vertices
+    );
+}
+
+#[test]
+fn load_from_data_uri_rejects_plain_data() {
+    assert!(Mesh::from_data_uri("data:model/obj,v 0 0 0").is_err());
+}
+
+#[test]
+fn load_quad_face() {
+    let obj = "
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.0 1.0 0.0
+v 0.0 1.0 0.0
+vn 0.0 0.0 1.0
+f 1//1 2//1 3//1 4//1
+";
+    let mesh = Mesh::from_reader(obj.as_bytes()).unwrap();
+
+    assert_eq!(2, mesh.triangles.len());
+    assert_eq!(
+        [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)],
+        mesh.triangles[0].vertices
+    );
+    assert_eq!(
+        [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)],
+        mesh.triangles[1].vertices
+    );
+}
+
+#[test]
+fn load_pentagon_face() {
+    let obj = "
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.5 1.0 0.0
+v 0.5 2.0 0.0
+v -0.5 1.0 0.0
+vn 0.0 0.0 1.0
+f 1//1 2//1 3//1 4//1 5//1
+";
+    assert_eq!(3, Mesh::from_reader(obj.as_bytes()).unwrap().triangles.len());
+}
+
+#[test]
+fn load_degenerate_face() {
+    let obj = "
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+vn 0.0 0.0 1.0
+f 1//1 2//1
+";
+    assert!(Mesh::from_reader(obj.as_bytes()).is_err());
+}
+
+#[test]
+fn load_face_with_out_of_range_index() {
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 1 2 99\n";
+
+    let err = Mesh::from_reader(obj.as_bytes()).err().unwrap();
+    assert_eq!("line 5: vertex 99 is referenced but only 3 defined", err.to_string());
+}
+
+#[test]
+fn load_face_with_malformed_index() {
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 x\n";
+
+    let err = Mesh::from_reader(obj.as_bytes()).err().unwrap();
+    assert_eq!("line 4: vertex index `x` is not an integer", err.to_string());
+}
+
+#[test]
+fn load_malformed_vertex() {
+    let obj = "v 0 0 0\nv 1 0 x\n";
+
+    let err = Mesh::from_reader(obj.as_bytes()).err().unwrap();
+    assert_eq!("line 2: `x` is not a number", err.to_string());
+
+    let err = Mesh::from_reader("v 0 0 0\nvn 0 0\n".as_bytes()).err().unwrap();
+    assert_eq!("line 2: invalid `vn` token", err.to_string());
+
+    let err = Mesh::from_reader("v 0 0 0\nv 1 0 0\nf 1 2\n".as_bytes()).err().unwrap();
+    assert_eq!("line 3: face must have at least 3 vertices, got 2", err.to_string());
+}
+
+#[test]
+fn load_face_with_negative_index() {
+    let mesh = load_single_face("f -3 -2 -1");
+
+    assert_eq!(load_single_face("f 1 2 3").triangles[0].vertices, mesh.triangles[0].vertices);
+}
+
+#[test]
+fn load_relative_indices() {
+    let obj = |first: &str, second: &str| {
+        format!(
+            "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 -1
+vn 0 1 0
+{}
+v 0 0 1
+vt 1 1
+vn 1 0 0
+{}
+",
+            first, second
+        )
+    };
+
+    let relative = Mesh::from_reader(obj("f -3/-3/-2 -2/-2/-1 -1/-1/-2", "f -4/-4/-3 -2/-2/-1 -1/-1/-1").as_bytes()).unwrap();
+    let absolute = Mesh::from_reader(obj("f 1/1/1 2/2/2 3/3/1", "f 1/1/1 3/3/3 4/4/3").as_bytes()).unwrap();
+
+    assert_eq!(absolute.triangles.len(), relative.triangles.len());
+    for (a, r) in absolute.triangles.iter().zip(&relative.triangles) {
+        assert_eq!(a.vertices, r.vertices);
+        assert_eq!(a.normals, r.normals);
+        assert_eq!(a.uvs, r.uvs);
+    }
+}
+
+#[cfg(test)]
+fn load_single_face(face: &str) -> Mesh {
+    let obj = format!(
+        "
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.0
+vt 0.0 1.0
+vn 0.0 0.0 -1.0
+vn 0.0 0.0 -1.0
+vn 0.0 0.0 -1.0
+{}
+",
+        face
+    );
+
+    Mesh::from_reader(obj.as_bytes()).unwrap()
+}
+
+#[test]
+fn load_face_without_normals() {
+    let mesh = load_single_face("f 1 2 3");
+
+    assert_eq!([Vec3::new(0.0, 0.0, 1.0); 3], mesh.triangles[0].normals);
+}
+
+#[test]
+fn load_face_with_texture_coordinates() {
+    let mesh = load_single_face("f 1/1 2/2 3/3");
+
+    assert_eq!([Vec3::new(0.0, 0.0, 1.0); 3], mesh.triangles[0].normals);
+}
+
+#[test]
+fn load_face_with_normals() {
+    let mesh = load_single_face("f 1//1 2//2 3//3");
+
+    assert_eq!([Vec3::new(0.0, 0.0, -1.0); 3], mesh.triangles[0].normals);
+}
+
+#[test]
+fn bounds_follow_transform() {
+    let mut mesh = cube(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+    assert_eq!(Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)), mesh.bounds());
+
+    mesh.transform(&Matrix4x4::new([
+        [1.0, 0.0, 0.0, 2.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]));
+
+    assert_eq!(Aabb::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0)), mesh.bounds());
+}
+
+#[cfg(test)]
+fn random_mesh(count: usize, next: &mut FnMut() -> f64) -> Mesh {
+    let mut triangles = Vec::with_capacity(count);
+    for _ in 0..count {
+        let center = Vec3::new(next() * 20.0 - 10.0, next() * 20.0 - 10.0, next() * 20.0 - 10.0);
+        let mut vertex = || center + Vec3::new(next() * 2.0 - 1.0, next() * 2.0 - 1.0, next() * 2.0 - 1.0);
+        let vertices = [vertex(), vertex(), vertex()];
+
+        triangles.push(Triangle::new(vertices));
+    }
+
+    Mesh::new(triangles)
+}
+
+#[test]
+fn bvh_matches_linear_intersection() {
+    // Simple LCG, because the exact distribution doesn't matter here.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+
+    let mesh = random_mesh(500, &mut next);
+
+    let mut hits = 0;
+    for _ in 0..2000 {
+        let origin = Vec3::new(next() * 30.0 - 15.0, next() * 30.0 - 15.0, -20.0);
+        let target = Vec3::new(next() * 20.0 - 10.0, next() * 20.0 - 10.0, next() * 20.0 - 10.0);
+        let ray = Ray::new(origin, target - origin, 0.0..f64::INFINITY);
+
+        let expected = mesh.intersection_linear(&ray).map(|i| i.t);
+        let actual = mesh.intersection(&ray).map(|i| i.t);
+        assert_eq!(expected, actual);
+
+        if actual.is_some() {
+            hits += 1;
+        }
+    }
+
+    assert!(hits > 0);
+}
+
+#[test]
+fn load_large_mesh() {
+    // A strip of 2000 triangles, i.e. more than 4000 lines.
+    let mut obj = String::new();
+    for i in 0..1001 {
+        obj.push_str(&format!("v {} 0 0\nv {} 1 0\n", i, i));
+    }
+    for i in 0..1000 {
+        let (a, b, c, d) = (2 * i + 1, 2 * i + 2, 2 * i + 3, 2 * i + 4);
+        obj.push_str(&format!("f {} {} {}\nf {} {} {}\n", a, c, b, b, c, d));
+    }
+
+    let mesh = Mesh::from_reader(obj.as_bytes()).unwrap();
+
+    assert_eq!(2000, mesh.triangles.len());
+}
+
+#[test]
+fn parallel_load_matches_serial() {
+    // A grid of quads with normals and texture coordinates at every vertex.
+    let n = 100;
+    let mut obj = String::new();
+    for y in 0..=n {
+        for x in 0..=n {
+            obj.push_str(&format!("v {} {} {}\nvt {} {}\nvn 0 {} 1\n", x, y, (x * y) % 7, x, y, x % 3));
+        }
+    }
+    for y in 0..n {
+        for x in 0..n {
+            let idx = |x, y| y * (n + 1) + x + 1;
+            let (a, b, c, d) = (idx(x, y), idx(x + 1, y), idx(x + 1, y + 1), idx(x, y + 1));
+            obj.push_str(&format!("f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2} {3}/{3}/{3}\n", a, b, c, d));
+        }
+    }
+
+    let parsed = Obj::parse(obj.as_bytes()).unwrap();
+    let serial: Vec<Triangle<f64>> = parsed.faces.iter().map(|face| parsed.triangle(face)).collect();
+    let mesh = Mesh::from_reader(obj.as_bytes()).unwrap();
+
+    assert_eq!(2 * n * n, mesh.triangles.len());
+    assert_eq!(serial, mesh.triangles);
+}
+
+#[test]
+fn intersection_reports_triangle() {
+    let mesh = cube(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+
+    for &(origin, direction) in &[
+        (Vec3::new(0.2, 0.3, -5.0), Vec3::new(0.0, 0.0, 1.0)),
+        (Vec3::new(-0.4, 5.0, 0.1), Vec3::new(0.0, -1.0, 0.0)),
+        (Vec3::new(5.0, -0.6, 0.7), Vec3::new(-1.0, 0.0, 0.0)),
+    ] {
+        let ray = Ray::new(origin, direction, 0.0..f64::INFINITY);
+        let intersection = mesh.intersection(&ray).unwrap();
+
+        let triangle = &mesh.triangles[intersection.primitive as usize];
+        assert_eq!(Some(intersection.t), triangle.intersection(&ray).map(|i| i.t));
+    }
+}
+
+#[test]
+fn load_face_with_uvs() {
+    let obj = "
+v 0.0 0.0 5.0
+v 3.0 0.0 5.0
+v 0.0 3.0 5.0
+vt 0.0 0.0
+vt 1.0 0.25
+vt 0.5 1.0
+f 1/1 2/2 3/3
+";
+    let mesh = Mesh::from_reader(obj.as_bytes()).unwrap();
+    let ray = Ray::new(Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..f64::INFINITY);
+
+    let uv = mesh.intersection(&ray).unwrap().uv;
+    assert!((uv.x - 0.5).abs() < 1e-9);
+    assert!((uv.y - 0.4166666666666667).abs() < 1e-9);
+}
+
+#[cfg(test)]
+const CUBE_WITHOUT_NORMALS: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2 3 7 6
+";
+
+#[test]
+fn load_cube_without_normals() {
+    let mesh = Mesh::from_reader(CUBE_WITHOUT_NORMALS.as_bytes()).unwrap();
+
+    // Rays hitting the face near the corner still see the flat normal.
+    let ray = Ray::new(Vec3::new(0.1, 0.1, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+    assert_eq!(Vec3::new(0.0, 0.0, -1.0), mesh.intersection(&ray).unwrap().normal);
+
+    for triangle in &mesh.triangles {
+        assert_eq!(triangle.normals[0], triangle.normals[1]);
+        assert_eq!(triangle.normals[0], triangle.normals[2]);
+    }
+}
+
+#[test]
+fn smooth_normals_average_shared_vertices() {
+    let mesh = Mesh::from_reader(CUBE_WITHOUT_NORMALS.as_bytes()).unwrap().with_smooth_normals();
+
+    for triangle in &mesh.triangles {
+        for i in 0..3 {
+            // Corners are shared by three perpendicular faces, so normals point
+            // away from the cube center along the diagonal.
+            let expected = (triangle.vertices[i] - Vec3::new(0.5, 0.5, 0.5)).unit();
+            assert!(triangle.normals[i].distance(&expected) < 1e-9);
         }
     }
 }
+
+#[test]
+fn smooth_normals_skip_degenerate_triangles() {
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nf 1 2 3\nf 1 2 4\nf 1 1 3\n";
+    let mesh = Mesh::from_reader(obj.as_bytes()).unwrap().with_smooth_normals();
+    assert_eq!(3, mesh.triangles.len());
+
+    // The collinear and the collapsed triangles don't tilt the normals of the
+    // vertices they share with the flat one.
+    for triangle in &mesh.triangles {
+        for (i, normal) in triangle.normals.iter().enumerate() {
+            if triangle.vertices[i] != Vec3::new(2.0, 0.0, 0.0) {
+                assert_eq!(Vec3::new(0.0, 0.0, 1.0), *normal);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+const TETRAHEDRON_STL: &str = "solid tetrahedron
+facet normal 0 0 -1
+  outer loop
+    vertex 0 0 0
+    vertex 0 1 0
+    vertex 1 0 0
+  endloop
+endfacet
+facet normal 0 -1 0
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 0 1
+  endloop
+endfacet
+facet normal -1 0 0
+  outer loop
+    vertex 0 0 0
+    vertex 0 0 1
+    vertex 0 1 0
+  endloop
+endfacet
+facet normal 0 0 0
+  outer loop
+    vertex 1 0 0
+    vertex 0 1 0
+    vertex 0 0 1
+  endloop
+endfacet
+endsolid tetrahedron
+";
+
+/// Encodes the facets as a binary STL.
+#[cfg(test)]
+fn binary_stl(facets: &[Facet]) -> Vec<u8> {
+    // Binary files starting with `solid` must not be taken for ASCII ones.
+    let mut bytes = b"solid binary".to_vec();
+    bytes.resize(80, 0);
+    bytes.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+
+    for (normal, vertices) in facets {
+        for v in std::iter::once(normal).chain(vertices) {
+            for &c in &[v.x, v.y, v.z] {
+                bytes.extend_from_slice(&(c as f32).to_bits().to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&[0, 0]);
+    }
+
+    bytes
+}
+
+#[test]
+fn load_ascii_and_binary_stl() {
+    let ascii = Mesh::from_stl(TETRAHEDRON_STL.as_bytes()).unwrap();
+    let facets = stl_ascii_facets(TETRAHEDRON_STL.as_bytes()).unwrap();
+    let binary = Mesh::from_stl(&binary_stl(&facets)).unwrap();
+
+    assert_eq!(4, ascii.triangles.len());
+    assert_eq!(ascii.triangles.len(), binary.triangles.len());
+    assert_eq!(Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)), ascii.bounds());
+    assert_eq!(ascii.bounds(), binary.bounds());
+
+    // Normals come from the file, unless they are zero.
+    assert_eq!([Vec3::new(0.0, 0.0, -1.0); 3], ascii.triangles[0].normals);
+    assert_eq!(ascii.triangles[3].normals, Triangle::new(ascii.triangles[3].vertices).normals);
+    assert_eq!(ascii.triangles[0].normals, binary.triangles[0].normals);
+}
+
+#[test]
+fn load_stl_with_incomplete_facet() {
+    let stl = "solid broken\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\nendfacet\n";
+
+    let err = Mesh::from_stl(stl.as_bytes()).err().unwrap();
+    assert_eq!("line 7: facet must have 3 vertices, got 2", err.to_string());
+}
@@ -1,21 +1,166 @@
-use crate::{ray::Ray, Intersection, Material};
+use crate::{matrix::Matrix4x4, ray::Ray, vec3::Vec3, Intersection, Material};
 
 pub use self::{
-    mesh::{Mesh, Triangle},
+    aabb::Aabb,
+    bvh::Bvh,
+    clip::ClipPlane,
+    cuboid::Cuboid,
+    cylinder::Cylinder,
+    disk::Disk,
+    instance::MeshInstance,
+    mesh::{Culling, Mesh, Triangle},
     plane::Plane,
     sphere::Sphere,
+    volume::VolumeBox,
 };
 use crate::transform::Transform;
 
+mod aabb;
+mod bvh;
+mod clip;
+mod cuboid;
+mod cylinder;
+mod disk;
+mod instance;
 mod mesh;
 mod plane;
 mod sphere;
+mod volume;
 
-pub trait Geometry: Transform<f64> {
+#[cfg(test)]
+pub use self::mesh::cube;
+
+pub trait Geometry: Transform<f64> + BoxClone {
     fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection>;
+
+    /// Returns the box enclosing the geometry, or `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+/// Clones geometry behind a trait object.
+pub trait BoxClone {
+    fn box_clone(&self) -> Box<Geometry + Sync>;
+}
+
+impl<T: Geometry + Clone + Sync + 'static> BoxClone for T {
+    fn box_clone(&self) -> Box<Geometry + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+/// Returns the box enclosing a circle with the given center, unit normal and
+/// radius.
+fn circle_bounds(center: Vec3<f64>, normal: Vec3<f64>, radius: f64) -> Aabb {
+    let extent = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+    let extent = Vec3::new(extent(normal.x), extent(normal.y), extent(normal.z));
+
+    Aabb::new(center - extent, center + extent)
+}
+
+/// Returns the factor radii of round shapes are scaled by.
+///
+/// Only uniform scaling keeps them round, which is all scenes accept for them,
+/// other transformations scale radii by the average over the axes.
+fn radius_scale(transformation: &Matrix4x4<f64>) -> f64 {
+    transformation.uniform_scale().unwrap_or_else(|| {
+        let scales = transformation.axis_scales();
+        (scales.x + scales.y + scales.z) / 3.0
+    })
 }
 
 pub struct Model<G> {
     pub geometry: G,
     pub material: Material,
+    /// Transformation applied to the geometry, see `set_transform`.
+    pub transform: Matrix4x4<f64>,
+    /// Planes cutting the model, transformed along with the geometry.
+    pub clip_planes: Vec<ClipPlane>,
+    /// Geometry and clip planes as they were before the first transformation.
+    base: Option<(G, Vec<ClipPlane>)>,
+}
+
+impl<G> Model<G> {
+    pub fn new(geometry: G, material: Material) -> Self {
+        Self {
+            geometry,
+            material,
+            transform: Matrix4x4::identity(),
+            clip_planes: Vec::new(),
+            base: None,
+        }
+    }
+}
+
+impl Model<Box<Geometry + Sync>> {
+    /// Replaces the transformation of the model.
+    ///
+    /// Geometry is transformed in place, so the untransformed geometry and
+    /// clip planes are kept on the first call and every later transformation
+    /// is applied to them instead. Clip planes added after that are dropped.
+    pub fn set_transform(&mut self, transform: Matrix4x4<f64>) {
+        if self.base.is_none() {
+            self.base = Some((self.geometry.box_clone(), self.clip_planes.clone()));
+        }
+
+        if let Some((geometry, clip_planes)) = &self.base {
+            self.geometry = geometry.box_clone();
+            self.geometry.transform(&transform);
+
+            self.clip_planes = clip_planes.clone();
+            for plane in &mut self.clip_planes {
+                plane.transform(&transform);
+            }
+        }
+
+        self.transform = transform;
+    }
+}
+
+impl<G: Geometry + ?Sized> Model<Box<G>> {
+    /// Intersects the ray with the model, skipping the parts hidden by its
+    /// clip planes.
+    ///
+    /// Returns the material of the surface hit, which is the cap material
+    /// when the ray hits the cut through a solid.
+    pub fn hit(&self, ray: &Ray<f64>) -> Option<(&Material, Intersection)> {
+        let clipped = |point: &Vec3<f64>| self.clip_planes.iter().any(|plane| plane.clips(point));
+
+        let range = ray.range();
+        let mut ray = Ray::new(ray.origin(), *ray.direction(), range.clone());
+
+        loop {
+            let intersection = self.geometry.intersection(&ray)?;
+            if !ray.contains(intersection.t) {
+                return None;
+            }
+
+            let plane = match self.clip_planes.iter().find(|plane| plane.clips(&intersection.point)) {
+                Some(plane) => plane,
+                None => return Some((&self.material, intersection)),
+            };
+
+            // The hit is cut away, but the ray may enter the visible side of
+            // the plane further along.
+            let t = match plane.entry(&ray) {
+                Some(t) if t > intersection.t && t < range.end => t,
+                Some(..) | None => return None,
+            };
+
+            ray = Ray::new(ray.origin(), *ray.direction(), t..range.end);
+
+            // Seeing the back of the surface from the plane means it cuts
+            // through the inside of a solid.
+            if let Some(cap) = plane.cap() {
+                let point = ray.offset(t);
+                let inside = match self.geometry.intersection(&ray) {
+                    Some(next) => ray.contains(next.t) && next.normal.dot(ray.direction()) > 0.0,
+                    None => false,
+                };
+
+                if inside && !clipped(&point) {
+                    return Some((cap, Intersection::new(t, point, plane.normal().inverse())));
+                }
+            }
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{geometry::Geometry, vec3::Vec3, Intersection, Ray};
+use crate::{geometry::{Aabb, Geometry}, vec3::Vec3, Intersection, Ray};
 use crate::transform::Transform;
 use crate::matrix::Matrix4x4;
 use crate::vec4::Vec4;
@@ -13,6 +13,12 @@ pub struct Plane {
     normal: Vec3<f64>,
 }
 
+impl Plane {
+    pub fn new(point: Vec3<f64>, normal: Vec3<f64>) -> Self {
+        Self { point, normal }
+    }
+}
+
 impl Geometry for Plane {
     fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
         let denominator = self.normal.dot(ray.direction());
@@ -20,16 +26,69 @@ impl Geometry for Plane {
         if denominator.abs() >= 1e-6 {
             let p0r0 = self.point - ray.origin();
             let t = p0r0.dot(&self.normal) / denominator;
+            if !ray.contains(t) {
+                return None;
+            }
+
             Some(Intersection::new(t, ray.origin() + ray.direction().scale(t), self.normal))
         } else {
             None
         }
     }
+
+    /// Planes are infinite, so no box encloses them.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
 }
 
 impl Transform<f64> for Plane {
     fn transform(&mut self, transformation: &Matrix4x4<f64>) {
         self.point = (transformation * Vec4::from(self.point)).into();
-        self.normal = (transformation * Vec4::from(self.normal)).into();
+        let normal: Vec3<f64> = (&transformation.normal_matrix() * Vec4::direction(self.normal)).into();
+        self.normal = normal.unit();
     }
 }
+
+#[test]
+fn transform_normal_with_non_uniform_scale() {
+    let mut plane = Plane::new(Vec3::default(), Vec3::new(1.0, 1.0, 0.0).unit());
+    plane.transform(&Matrix4x4::scaling(Vec3::new(2.0, 1.0, 1.0)));
+
+    // Direction lying on the plane before the transformation, then scaled.
+    let tangent = Vec3::new(2.0, -1.0, 0.0);
+
+    assert!(plane.normal.dot(&tangent).abs() < 1e-12);
+    assert!((plane.normal.len() - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn bounding_box() {
+    assert_eq!(None, Plane::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0)).bounding_box());
+}
+
+#[test]
+fn intersection_in_front() {
+    let plane = Plane::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    let intersection = plane.intersection(&ray).unwrap();
+    assert_eq!(5.0, intersection.t);
+    assert_eq!(Vec3::new(0.0, 0.0, 5.0), intersection.point);
+}
+
+#[test]
+fn intersection_behind() {
+    let plane = Plane::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    assert!(plane.intersection(&ray).is_none());
+}
+
+#[test]
+fn intersection_beyond_range() {
+    let plane = Plane::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 0.0..4.0);
+
+    assert!(plane.intersection(&ray).is_none());
+}
@@ -1,4 +1,11 @@
-use crate::{geometry::Geometry, matrix::Matrix4x4, transform::Transform, vec3::Vec3, vec4::Vec4, Intersection, Ray};
+use crate::{
+    geometry::{radius_scale, Aabb, Geometry},
+    matrix::Matrix4x4,
+    transform::Transform,
+    vec3::Vec3,
+    vec4::Vec4,
+    Intersection, Ray,
+};
 
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct Sphere {
@@ -6,6 +13,12 @@ pub struct Sphere {
     radius: f64,
 }
 
+impl Sphere {
+    pub fn new(center: Vec3<f64>, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
 impl Geometry for Sphere {
     fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
         let oc = ray.origin() - self.center;
@@ -22,21 +35,72 @@ impl Geometry for Sphere {
         let sqrt = discriminant.sqrt();
         let denominator = 2.0 * a;
 
-        let x1 = (-b + sqrt) / denominator;
-        let x2 = (-b - sqrt) / denominator;
+        let near = (-b - sqrt) / denominator;
+        let far = (-b + sqrt) / denominator;
 
-        let t = if x1 < x2 { x1 } else { x2 };
+        // The near root lies behind the origin when the ray starts inside the
+        // sphere.
+        let t = if near > 0.0 && ray.contains(near) {
+            near
+        } else if far > 0.0 && ray.contains(far) {
+            far
+        } else {
+            return None;
+        };
 
         let intersection = ray.offset(t);
         let normal = (intersection - self.center).unit();
 
         return Some(Intersection::new(t, intersection, normal));
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
 }
 
 impl Transform<f64> for Sphere {
     fn transform(&mut self, transformation: &Matrix4x4<f64>) {
         let vec4 = Vec4::new(self.center.x, self.center.y, self.center.z, 1.0);
         self.center = (transformation * vec4).into();
+        self.radius *= radius_scale(transformation);
     }
 }
+
+#[test]
+fn intersection_from_inside() {
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0);
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    let intersection = sphere.intersection(&ray).unwrap();
+    assert_eq!(1.0, intersection.t);
+    assert_eq!(Vec3::new(0.0, 0.0, 2.0), intersection.point);
+    assert_eq!(Vec3::new(0.0, 0.0, 1.0), intersection.normal);
+}
+
+#[test]
+fn intersection_pointing_away() {
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0);
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0..1.0e20);
+
+    assert!(sphere.intersection(&ray).is_none());
+}
+
+#[test]
+fn intersection_in_front() {
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0);
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..1.0e20);
+
+    assert_eq!(4.0, sphere.intersection(&ray).unwrap().t);
+}
+
+#[test]
+fn bounding_box() {
+    let sphere = Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0);
+
+    assert_eq!(
+        Some(Aabb::new(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(3.0, 4.0, 5.0))),
+        sphere.bounding_box()
+    );
+}
@@ -0,0 +1,93 @@
+//! Homogeneous participating medium bounded by an axis-aligned box.
+
+use std::f64;
+
+use crate::{geometry::Aabb, matrix::Matrix4x4, transform::Transform, vec3::Vec3, vec4::Vec4, Ray};
+
+/// A region of homogeneous scattering medium, like fog or smoke.
+///
+/// Light travelling through the box is attenuated by the Beer-Lambert law and
+/// part of the attenuated light is scattered back towards the viewer.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct VolumeBox {
+    min: Vec3<f64>,
+    max: Vec3<f64>,
+    /// Scattering coefficient, i.e. how dense the medium is.
+    density: f64,
+    /// Fraction of the extinguished light that is scattered rather than
+    /// absorbed.
+    albedo: f64,
+}
+
+impl VolumeBox {
+    pub fn new(min: Vec3<f64>, max: Vec3<f64>, density: f64, albedo: f64) -> Self {
+        Self { min, max, density, albedo }
+    }
+
+    #[inline]
+    pub fn albedo(&self) -> f64 {
+        self.albedo
+    }
+
+    /// Returns the `(t0, t1)` interval the ray spends inside the box, clipped
+    /// to the ray range.
+    #[inline]
+    pub fn interval(&self, ray: &Ray<f64>) -> Option<(f64, f64)> {
+        Aabb::new(self.min, self.max).interval(ray)
+    }
+
+    /// Fraction of light passing through the given distance of the medium.
+    #[inline]
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        (-self.density * distance).exp()
+    }
+}
+
+/// Transforms the corners and takes their bounds, like `Cuboid` does, as the
+/// medium stays axis-aligned.
+impl Transform<f64> for VolumeBox {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        let corners: Vec<Vec3<f64>> = (0..8)
+            .map(|idx| {
+                let corner = Vec3::new(
+                    if idx & 1 == 0 { self.min.x } else { self.max.x },
+                    if idx & 2 == 0 { self.min.y } else { self.max.y },
+                    if idx & 4 == 0 { self.min.z } else { self.max.z },
+                );
+                (transformation * Vec4::from(corner)).into()
+            })
+            .collect();
+
+        let bounds = Aabb::from_points(&corners);
+        self.min = bounds.min;
+        self.max = bounds.max;
+    }
+}
+
+#[test]
+fn interval_through_box() {
+    let volume = VolumeBox::new(Vec3::new(-1.0, -1.0, 1.0), Vec3::new(1.0, 1.0, 3.0), 1.0, 0.0);
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 0.0..f64::INFINITY);
+
+    assert_eq!(Some((1.0, 3.0)), volume.interval(&ray));
+}
+
+#[test]
+fn interval_misses_box() {
+    let volume = VolumeBox::new(Vec3::new(-1.0, -1.0, 1.0), Vec3::new(1.0, 1.0, 3.0), 1.0, 0.0);
+    let ray = Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0..f64::INFINITY);
+
+    assert_eq!(None, volume.interval(&ray));
+}
+
+#[test]
+fn rotated_box_encloses_corners() {
+    let mut volume = VolumeBox::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), 1.0, 0.0);
+    volume.transform(&Matrix4x4::rotation_z(f64::consts::FRAC_PI_4));
+
+    let ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0..f64::INFINITY);
+    let (t0, t1) = volume.interval(&ray).unwrap();
+
+    assert!((t0 - (5.0 - f64::consts::SQRT_2)).abs() < 1e-9, "{}", t0);
+    assert!((t1 - (5.0 + f64::consts::SQRT_2)).abs() < 1e-9, "{}", t1);
+}
@@ -0,0 +1,160 @@
+//! End-to-end checks rendering every `*.json` scene in `tests/golden` and
+//! comparing it against the committed image with the same name next to it.
+//!
+//! Images are stored as binary PPM, see `headless::write_ppm`, rather than as
+//! PNG. PPM is written and read here without the `image` codecs, so that the
+//! comparison doesn't depend on their versions, and the raw pixels are easy to
+//! inspect. Run the tests with `PHOTON_UPDATE_GOLDENS=1` to regenerate them
+//! after intended changes.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::{headless, Scene};
+
+/// Largest difference of a channel from the golden image that still passes,
+/// so that rounding differences between platforms don't fail the tests.
+const TOLERANCE: u8 = 2;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// Parses a binary PPM as written by `write_ppm`, returning the width, height
+/// and RGB pixels.
+fn read_ppm(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    // The header is four whitespace separated fields, the last one followed by
+    // a single whitespace character.
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while pos < bytes.len() && (bytes[pos] as char).is_whitespace() {
+            pos += 1;
+        }
+        let start = pos;
+        while pos < bytes.len() && !(bytes[pos] as char).is_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("truncated PPM header".into());
+        }
+        fields.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+    }
+
+    let number = |field: &str| field.parse::<u32>().map_err(|_| format!("invalid PPM header field `{}`", field));
+    if fields[0] != "P6" || number(&fields[3])? != 255 {
+        return Err(format!("unsupported PPM `{} {}`", fields[0], fields[3]));
+    }
+    let (width, height) = (number(&fields[1])?, number(&fields[2])?);
+
+    if pos >= bytes.len() {
+        return Err("missing PPM pixels".into());
+    }
+
+    let pixels = bytes[pos + 1..].to_vec();
+    if pixels.len() != 3 * width as usize * height as usize {
+        return Err(format!("expected {}x{} pixels, got {} bytes", width, height, pixels.len()));
+    }
+
+    Ok((width, height, pixels))
+}
+
+/// Compares the rendered pixels against the golden ones, describing the worst
+/// pixel when it differs more than `TOLERANCE`.
+fn compare(width: u32, actual: &[u8], expected: &[u8]) -> Result<(), String> {
+    let (idx, delta) = actual
+        .iter()
+        .zip(expected)
+        .map(|(&a, &e)| if a > e { a - e } else { e - a })
+        .enumerate()
+        .max_by_key(|&(_, delta)| delta)
+        .unwrap_or((0, 0));
+
+    if delta <= TOLERANCE {
+        return Ok(());
+    }
+
+    let pixel = idx / 3;
+    let (x, y) = (pixel as u32 % width, pixel as u32 / width);
+    let rgb = |buf: &[u8]| [buf[3 * pixel], buf[3 * pixel + 1], buf[3 * pixel + 2]];
+
+    Err(format!(
+        "max channel delta {} at pixel ({}, {}): expected {:?}, got {:?}",
+        delta,
+        x,
+        y,
+        rgb(expected),
+        rgb(actual)
+    ))
+}
+
+/// Renders the scene with its own camera and checks it against the golden
+/// image next to it, or replaces the image when asked to.
+fn check_golden(path: &Path) -> Result<(), String> {
+    let scene = Scene::load(&path).map_err(|err| err.to_string())?;
+    let camera = scene.camera;
+    let (width, height) = (camera.width, camera.height);
+    let actual = scene.render(&camera, width, height).into_raw();
+
+    let path = path.with_extension("ppm");
+    if env::var_os("PHOTON_UPDATE_GOLDENS").is_some() {
+        let file = File::create(&path).map_err(|err| err.to_string())?;
+        return headless::write_ppm(&actual, width, height, BufWriter::new(file)).map_err(|err| err.to_string());
+    }
+
+    let bytes = fs::read(&path).map_err(|err| format!("{}, set PHOTON_UPDATE_GOLDENS=1 to create it", err))?;
+    let (golden_width, golden_height, expected) = read_ppm(&bytes)?;
+    if (width, height) != (golden_width, golden_height) {
+        return Err(format!("expected {}x{} image, got {}x{}", golden_width, golden_height, width, height));
+    }
+
+    compare(width, &actual, &expected)
+}
+
+#[test]
+fn golden_scenes() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(golden_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scenes in {}", golden_dir().display());
+
+    let failures: Vec<String> = paths
+        .iter()
+        .filter_map(|path| check_golden(path).err().map(|err| format!("{}: {}", path.display(), err)))
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn read_written_ppm() {
+    let pixels = vec![1, 2, 3, 4, 5, 6];
+    let mut bytes = Vec::new();
+    headless::write_ppm(&pixels, 2, 1, &mut bytes).unwrap();
+
+    assert_eq!(Ok((2, 1, pixels)), read_ppm(&bytes));
+    assert!(read_ppm(b"P6\n2 1\n255\n\x01").is_err());
+    assert!(read_ppm(b"P6\n2 1\n255").is_err());
+    assert!(read_ppm(b"P3\n1 1\n255\n\x01\x02\x03").is_err());
+}
+
+#[test]
+fn compare_reports_worst_pixel() {
+    let expected = [10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10];
+    let mut actual = expected;
+    actual[1] = 12;
+    assert_eq!(Ok(()), compare(2, &actual, &expected));
+
+    actual[10] = 50;
+    assert_eq!(
+        Err("max channel delta 40 at pixel (1, 1): expected [10, 10, 10], got [10, 50, 10]".to_string()),
+        compare(2, &actual, &expected)
+    );
+}
@@ -0,0 +1,324 @@
+//! Tracing frames and saving images, shared by the window and the file output.
+//!
+//! Nothing here may depend on SDL, so that scenes can be rendered on machines
+//! without a display.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+#[cfg(test)]
+use image::Rgb;
+use rayon::prelude::*;
+
+use crate::{camera::Camera, sampling, vec3::Vec3, Scene};
+
+/// Side of the square tiles frames are split into, each traced by a single
+/// thread.
+const TILE_SIZE: u32 = 32;
+
+/// Traces the given frame of the scene into float colors row by row, leaving
+/// the conversion into bytes to `Scene::encode`.
+///
+/// The first frame of a single sample camera goes through pixel centers. Other
+/// frames jitter samples within pixels, continuing the sub-pixel offsets where
+/// the previous frame stopped, so that consecutive frames can be accumulated.
+///
+/// Progress is reported as the number of rows worth of pixels traced so far.
+/// Tiles are traced in parallel, so the count doesn't refer to any particular
+/// rows. Counts only grow, and the last one is `height`.
+pub fn trace_frame<F>(scene: &Scene, camera: &Camera, width: u32, height: u32, frame: u64, on_row: F) -> Vec<Vec3<f64>>
+where
+    F: Fn(u32) + Sync,
+{
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push((x, y, TILE_SIZE.min(width - x), TILE_SIZE.min(height - y)));
+        }
+    }
+
+    let traced = AtomicUsize::new(0);
+    // Largest count reported so far, so that racing threads never report
+    // progress going backwards.
+    let reported = Mutex::new(0);
+
+    let colors: Vec<Vec<Vec3<f64>>> = tiles
+        .par_iter()
+        .map(|&(x0, y0, w, h)| {
+            let mut colors = Vec::with_capacity((w * h) as usize);
+            for y in y0..y0 + h {
+                for x in x0..x0 + w {
+                    colors.push(trace_sample(scene, camera, x, y, width, height, frame));
+                }
+            }
+
+            let traced = traced.fetch_add(colors.len(), Ordering::SeqCst) + colors.len();
+            let rows = (traced / width as usize) as u32;
+            let mut reported = reported.lock().unwrap();
+            if rows > *reported {
+                *reported = rows;
+                on_row(rows);
+            }
+
+            colors
+        })
+        .collect();
+
+    let mut buf = vec![Vec3::default(); (width * height) as usize];
+    for (&(x0, y0, w, _), colors) in tiles.iter().zip(colors) {
+        for (n, color) in colors.into_iter().enumerate() {
+            let (x, y) = (x0 + n as u32 % w, y0 + n as u32 / w);
+            buf[(y * width + x) as usize] = color;
+        }
+    }
+
+    buf
+}
+
+/// Traces the color of the given pixel for the given frame.
+fn trace_sample(scene: &Scene, camera: &Camera, x: u32, y: u32, width: u32, height: u32, frame: u64) -> Vec3<f64> {
+    if frame == 0 && camera.samples <= 1 {
+        scene.trace_hdr(&camera.ray_for(x, y, width, height))
+    } else {
+        trace_pixel(scene, camera, x, y, width, height, frame * camera.samples as u64)
+    }
+}
+
+/// Averages `camera.samples` rays jittered within the pixel footprint and the
+/// camera lens, using the sub-pixel offsets starting at `first`.
+///
+/// Offsets are rotated differently in every pixel as determined by the scene
+/// seed.
+fn trace_pixel(scene: &Scene, camera: &Camera, x: u32, y: u32, width: u32, height: u32, first: u64) -> Vec3<f64> {
+    let rotation = sampling::scramble(scene.seed, (y * width + x) as u64);
+    let colors: Vec<Vec3<f64>> = (0..camera.samples.max(1) as u64)
+        .map(|sample| {
+            let offset = sampling::rotate(sampling::subpixel_offset(first + sample), rotation);
+            let lens = sampling::rotate(sampling::lens_offset(first + sample), rotation);
+            let lens = sampling::uniform_sample_disk(lens.x, lens.y);
+            scene.trace_hdr(&camera.lens_ray(x as f64 + offset.x, y as f64 + offset.y, width, height, lens))
+        })
+        .collect();
+
+    average(&colors)
+}
+
+/// Returns the mean of the given float colors, which are not rounded until
+/// the whole pixel is encoded.
+fn average(colors: &[Vec3<f64>]) -> Vec3<f64> {
+    let sum = colors.iter().fold(Vec3::default(), |sum, &color| sum + color);
+
+    sum.scale(1.0 / colors.len() as f64)
+}
+
+/// Renders the scene into the file, in the format given by its extension.
+pub fn render_to_file<P: AsRef<Path>>(scene: &Scene, camera: &Camera, width: u32, height: u32, path: P) -> Result<(), Box<dyn Error>> {
+    let image = scene.render(camera, width, height);
+
+    let ppm = path
+        .as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("ppm"));
+    if ppm {
+        write_ppm(&image.into_raw(), width, height, BufWriter::new(File::create(path)?))?;
+    } else {
+        image.save(path)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the image as a binary PPM (P6).
+///
+/// The buffer holds RGB pixels row by row, as rendered, rather than the BGRA
+/// ones of the window texture, see `bgra_to_image`.
+pub fn write_ppm<W: Write>(buf: &[u8], width: u32, height: u32, mut out: W) -> io::Result<()> {
+    if buf.len() != 3 * width as usize * height as usize {
+        let message = format!("expected {}x{} RGB pixels, got {} bytes", width, height, buf.len());
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(buf)?;
+    out.flush()
+}
+
+#[cfg(test)]
+fn sphere_scene() -> Scene {
+    use crate::{
+        geometry::{Model, Sphere},
+        Material, PointLight,
+    };
+
+    let mut scene = Scene::new(Rgb([30, 30, 30]));
+    // Keeps rendered colors equal to the traced ones.
+    scene.gamma = Some(1.0);
+    scene.ambient = 0.0;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 0.0, -5.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([255, 0, 0])),
+    ));
+    scene
+}
+
+#[test]
+fn render_without_window() {
+    let camera = Camera {
+        position: Vec3::default(),
+        target: Vec3::new(0.0, 0.0, 1.0),
+        ..Camera::default()
+    };
+    let image = sphere_scene().render(&camera, 8, 8);
+
+    assert_eq!(Rgb([30, 30, 30]), *image.get_pixel(0, 0));
+    assert_eq!(Rgb([234, 0, 0]), *image.get_pixel(4, 4));
+}
+
+#[test]
+fn render_reports_progress() {
+    let rows = Mutex::new(Vec::new());
+
+    sphere_scene().render_with_progress(&Camera::default(), 8, 6, |row| rows.lock().unwrap().push(row));
+
+    let rows = rows.into_inner().unwrap();
+    assert!(rows.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", rows);
+    assert_eq!(Some(&6), rows.last());
+}
+
+#[test]
+fn tiles_match_pixel_by_pixel() {
+    let camera = Camera {
+        position: Vec3::default(),
+        target: Vec3::new(0.0, 0.0, 1.0),
+        ..Camera::default()
+    };
+    // Neither side is a multiple of the tile size, so that edge tiles are
+    // partial.
+    let (width, height) = (TILE_SIZE * 2 + 5, TILE_SIZE + 7);
+    let scene = sphere_scene();
+
+    for frame in 0..2 {
+        let expected: Vec<_> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| trace_sample(&scene, &camera, x, y, width, height, frame))
+            .collect();
+
+        assert_eq!(expected, trace_frame(&scene, &camera, width, height, frame, |_| {}));
+    }
+}
+
+#[test]
+fn render_to_file_without_window() {
+    let path = std::env::temp_dir().join("photon-headless-test.png");
+
+    render_to_file(&sphere_scene(), &Camera::default(), 8, 8, &path).unwrap();
+
+    assert!(path.exists());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn does_not_use_sdl() {
+    // Tests never initialize SDL, so rendering above already runs without it.
+    // This keeps the module from referring to the crate at all, split so that
+    // the test doesn't match itself.
+    let source = include_str!("headless.rs");
+
+    assert!(!source.contains(concat!("sdl", "2")));
+}
+
+#[test]
+fn write_ppm_image() {
+    let pixels = [255, 0, 0, 0, 255, 0, 0, 0, 255, 1, 2, 3];
+    let mut out = Vec::new();
+
+    write_ppm(&pixels, 2, 2, &mut out).unwrap();
+
+    let mut expected = b"P6\n2 2\n255\n".to_vec();
+    expected.extend_from_slice(&pixels);
+    assert_eq!(expected, out);
+
+    assert!(write_ppm(&pixels, 3, 2, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn render_to_ppm_file() {
+    let path = std::env::temp_dir().join("photon-headless-test.ppm");
+
+    render_to_file(&sphere_scene(), &Camera::default(), 4, 2, &path).unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(bytes.starts_with(b"P6\n4 2\n255\n"));
+    assert_eq!(11 + 4 * 2 * 3, bytes.len());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn average_of_identical_samples() {
+    let color = Vec3::new(0.125, 0.25, 0.5);
+
+    assert_eq!(color, average(&[color]));
+    assert_eq!(color, average(&[color; 16]));
+}
+
+#[test]
+fn render_with_multiple_samples() {
+    let camera = Camera {
+        position: Vec3::default(),
+        target: Vec3::new(0.0, 0.0, 1.0),
+        samples: 8,
+        ..Camera::default()
+    };
+    let image = sphere_scene().render(&camera, 8, 8);
+
+    // Pixels fully outside or inside of the sphere are unaffected by jitter.
+    assert_eq!(Rgb([30, 30, 30]), *image.get_pixel(0, 0));
+    assert!(image.get_pixel(4, 4)[0] > 200);
+}
+
+#[test]
+fn render_with_seed() {
+    let camera = Camera {
+        position: Vec3::default(),
+        target: Vec3::new(0.0, 0.0, 1.0),
+        samples: 4,
+        ..Camera::default()
+    };
+    let render_seeded = |seed| {
+        let mut scene = sphere_scene();
+        scene.seed = seed;
+        scene.render(&camera, 16, 16).into_raw()
+    };
+
+    assert_eq!(render_seeded(1), render_seeded(1));
+    assert_ne!(render_seeded(1), render_seeded(2));
+}
+
+#[test]
+fn window_and_file_share_first_frame() {
+    let scene = sphere_scene();
+    let camera = Camera {
+        position: Vec3::default(),
+        target: Vec3::new(0.0, 0.0, 1.0),
+        ..Camera::default()
+    };
+
+    let frame = trace_frame(&scene, &camera, 8, 8, 0, |_| {});
+    let encoded: Vec<u8> = frame.iter().flat_map(|&color| scene.encode(color).data.to_vec()).collect();
+
+    assert_eq!(scene.render(&camera, 8, 8).into_raw(), encoded);
+}
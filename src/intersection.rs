@@ -1,14 +1,35 @@
-use crate::vec3::Vec3;
+use crate::{vec2::Vec2, vec3::Vec3};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Intersection {
     pub t: f64,
     pub point: Vec3<f64>,
     pub normal: Vec3<f64>,
+    /// Index of the primitive hit within its geometry, e.g. the triangle of a
+    /// mesh. Always zero for geometries made of a single primitive.
+    pub primitive: u32,
+    /// Texture coordinates of the hit point.
+    pub uv: Vec2<f64>,
 }
 
 impl Intersection {
     pub fn new(t: f64, point: Vec3<f64>, normal: Vec3<f64>) -> Self {
-        Self { t, point, normal }
+        Self {
+            t,
+            point,
+            normal,
+            primitive: 0,
+            uv: Vec2::default(),
+        }
+    }
+
+    pub fn with_primitive(mut self, primitive: u32) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    pub fn with_uv(mut self, uv: Vec2<f64>) -> Self {
+        self.uv = uv;
+        self
     }
 }
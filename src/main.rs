@@ -3,57 +3,330 @@
 #[macro_use]
 extern crate serde_derive;
 
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::error::Error;
 use std::f64;
 use std::fs::File;
+use std::io::Read;
 use std::path::Path;
-use std::time::Instant;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use rayon::prelude::*;
 use serde::{Deserialize, Deserializer};
 use image::{ImageBuffer, ImageRgb8, Pixel, Rgb};
 use sdl2::{event::Event, gfx::framerate::FPSManager, keyboard::Keycode, mouse::Cursor};
 
-use crate::geometry::{Geometry, Mesh, Model, Plane, Sphere};
+use crate::camera::Camera;
+use crate::color::{ColorSpace, ToneMapping};
+use crate::geometry::{Bvh, ClipPlane, Cuboid, Culling, Cylinder, Disk, Geometry, Mesh, MeshInstance, Model, Plane, Sphere, VolumeBox};
+use crate::manifest::Manifest;
 use crate::matrix::Matrix4x4;
 use crate::ray::Ray;
+use crate::stats::RenderStats;
+use crate::texture::Texture;
 use crate::transform::Transform;
 use crate::vec3::Vec3;
+use crate::vec4::Vec4;
 pub use crate::intersection::Intersection;
 
+mod camera;
+mod color;
 mod geometry;
+#[cfg(test)]
+mod golden;
+mod headless;
 mod intersection;
+mod manifest;
 mod matrix;
 mod ray;
+mod sampling;
+mod stats;
+mod texture;
 mod transform;
+mod vec2;
 mod vec3;
 mod vec4;
 
+/// Deserializes a color given either as an `[r, g, b]` triple or as a
+/// `#rrggbb` or `#rgb` hex string.
 fn deserialize_rgb<'de, D>(de: D) -> Result<Rgb<u8>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let (r, g, b) = Deserialize::deserialize(de)?;
-    let rgb = Rgb([r, g, b]);
+    struct RgbVisitor;
 
-    Ok(rgb)
+    impl<'de> serde::de::Visitor<'de> for RgbVisitor {
+        type Value = Rgb<u8>;
+
+        fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fmt.write_str("an `[r, g, b]` triple or a `#rrggbb` hex string")
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<Rgb<u8>, A::Error> {
+            let (r, g, b) = Deserialize::deserialize(serde::de::value::SeqAccessDeserializer::new(seq))?;
+
+            Ok(Rgb([r, g, b]))
+        }
+
+        fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Rgb<u8>, E> {
+            parse_hex_rgb(v).ok_or_else(|| E::custom(format!("invalid color `{}`, expected `#rrggbb` or `#rgb`", v)))
+        }
+    }
+
+    de.deserialize_any(RgbVisitor)
+}
+
+fn parse_hex_rgb(v: &str) -> Option<Rgb<u8>> {
+    if !v.starts_with('#') || !v[1..].chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+
+    let digits = &v[1..];
+    let channel = |idx: usize, len: usize| u8::from_str_radix(&digits[idx * len..(idx + 1) * len], 16).ok();
+    match digits.len() {
+        6 => Some(Rgb([channel(0, 2)?, channel(1, 2)?, channel(2, 2)?])),
+        // Short form repeats every digit, i.e. `#f80` is `#ff8800`.
+        3 => Some(Rgb([channel(0, 1)? * 17, channel(1, 1)? * 17, channel(2, 1)? * 17])),
+        _ => None,
+    }
+}
+
+fn deserialize_texture<'de, D>(de: D) -> Result<Option<Arc<Texture>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let path: String = Deserialize::deserialize(de)?;
+    let texture = if path.starts_with("data:") {
+        Texture::from_data_uri(&path)
+    } else {
+        Texture::load(&path)
+    };
+
+    texture.map(|texture| Some(Arc::new(texture))).map_err(serde::de::Error::custom)
 }
 
+/// Procedural coloring computed from the world space hit point.
 #[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Pattern {
+    /// Cubes of `scale` size alternating between the two colors.
+    Checker {
+        #[serde(deserialize_with = "deserialize_rgb")]
+        color_a: Rgb<u8>,
+        #[serde(deserialize_with = "deserialize_rgb")]
+        color_b: Rgb<u8>,
+        #[serde(default = "Pattern::unit_scale")]
+        scale: f64,
+    },
+}
+
+impl Pattern {
+    /// Returns the pattern color at the given intersection.
+    fn color_at(&self, intersection: &Intersection) -> Rgb<u8> {
+        match *self {
+            Pattern::Checker { color_a, color_b, scale } => {
+                // Hit points lie on the surface up to rounding errors, so they
+                // are moved below it to keep on one side of cell boundaries
+                // aligned with the surface, e.g. checkered floors.
+                let point = intersection.point - intersection.normal.unit().scale(1.0e-6);
+                let cell = |c: f64| (c / scale).floor() as i64;
+
+                if (cell(point.x) + cell(point.y) + cell(point.z)) % 2 == 0 {
+                    color_a
+                } else {
+                    color_b
+                }
+            }
+        }
+    }
+
+    fn unit_scale() -> f64 {
+        1.0
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct Material {
     #[serde(deserialize_with = "deserialize_rgb")]
     color: Rgb<u8>,
+    /// Fraction of light reflected at normal incidence. Surfaces reflect more
+    /// at grazing angles, and `0.0` turns reflections off entirely.
     reflective: f64,
+    /// Fraction of light passing through the surface, from opaque `0.0` to
+    /// invisible `1.0`.
+    #[serde(default)]
+    transparency: f64,
+    /// Phong specular exponent, the higher the smaller and sharper the
+    /// highlights are. Negative or missing `specular` turns highlights off.
+    #[serde(default = "Material::no_specular")]
+    specular: f64,
+    /// Index of refraction of the medium behind the surface, relative to the
+    /// one the ray comes from.
+    #[serde(default = "Material::vacuum")]
+    refractive_index: f64,
+    /// Image used instead of `color` when present, shared between copies of
+    /// the material.
+    #[serde(default, deserialize_with = "deserialize_texture")]
+    texture: Option<Arc<Texture>>,
+    /// Procedural pattern used instead of `color` when present.
+    #[serde(default)]
+    pattern: Option<Pattern>,
+    /// Brightness of the light the surface emits in its own color, added
+    /// regardless of the incident light. `0.0` means no emission.
+    #[serde(default)]
+    emissive: f64,
+}
+
+impl Material {
+    pub fn new(color: Rgb<u8>) -> Self {
+        Self {
+            color,
+            reflective: 0.0,
+            transparency: 0.0,
+            specular: Self::no_specular(),
+            refractive_index: Self::vacuum(),
+            texture: None,
+            pattern: None,
+            emissive: 0.0,
+        }
+    }
+
+    /// Returns the surface color at the given intersection.
+    fn color_at(&self, intersection: &Intersection) -> Rgb<u8> {
+        match (&self.texture, &self.pattern) {
+            (Some(texture), _) => texture.sample(&intersection.uv),
+            (None, Some(pattern)) => pattern.color_at(intersection),
+            (None, None) => self.color,
+        }
+    }
+
+    fn no_specular() -> f64 {
+        -1.0
+    }
+
+    fn vacuum() -> f64 {
+        1.0
+    }
+}
+
+/// Distance the shadow rays are moved away from the surface they start on.
+const SHADOW_BIAS: f64 = 1.0e-4;
+
+/// Converts the 8-bit color into the float one the tracer works with, where
+/// `1.0` is the full channel.
+fn to_float(color: Rgb<u8>) -> Vec3<f64> {
+    Vec3::new(color[0] as f64, color[1] as f64, color[2] as f64).scale(1.0 / 255.0)
+}
+
+/// Multiplies the float colors channel by channel, e.g. the surface color by
+/// the light falling on it.
+fn modulate(a: Vec3<f64>, b: Vec3<f64>) -> Vec3<f64> {
+    Vec3::new(a.x * b.x, a.y * b.y, a.z * b.z)
+}
+
+/// Linearly interpolates between two colors, `t = 0.0` giving the first one.
+///
+/// The blend is computed in floating point and clamped, because rounding of
+/// the two weighted parts may otherwise add up to more than `255`.
+fn mix(a: Rgb<u8>, b: Rgb<u8>, t: f64) -> Rgb<u8> {
+    let mut rgb = Rgb([0, 0, 0]);
+    for c in 0..3 {
+        let color = a[c] as f64 * (1.0 - t) + b[c] as f64 * t;
+
+        rgb[c] = if color > 255.0 { 255 } else { color as u8 };
+    }
+
+    rgb
+}
+
+/// Schlick's approximation of the Fresnel reflectance, given the cosine of the
+/// angle between the ray and the normal and the reflectance `f0` at normal
+/// incidence.
+///
+/// Surfaces reflect more at grazing angles, up to a mirror at `cos = 0`.
+fn schlick(cos: f64, f0: f64) -> f64 {
+    f0 + (1.0 - f0) * (1.0 - cos).max(0.0).min(1.0).powi(5)
+}
+
+/// Bends the unit `direction` crossing a surface with the given unit normal
+/// according to Snell's law.
+///
+/// The normal may point to either side, rays hitting the surface from the
+/// side it points away from are treated as leaving the medium. Returns `None`
+/// on total internal reflection.
+fn refract(direction: &Vec3<f64>, normal: &Vec3<f64>, refractive_index: f64) -> Option<Vec3<f64>> {
+    let cos = -normal.dot(direction);
+    let (normal, cos, eta) = if cos < 0.0 {
+        (normal.inverse(), -cos, refractive_index)
+    } else {
+        (*normal, cos, 1.0 / refractive_index)
+    };
+
+    let k = 1.0 - eta * eta * (1.0 - cos * cos);
+    if k < 0.0 {
+        return None;
+    }
+
+    Some(direction.scale(eta) + normal.scale(eta * cos - k.sqrt()))
+}
+
+/// Alpha-composites `(color, opacity)` layers sorted front to back over the
+/// background.
+///
+/// Layers are accumulated front to back, which gives the same result as
+/// blending back to front, but allows to stop once the opacity saturates.
+fn composite(layers: &[(Vec3<f64>, f64)], background: Vec3<f64>) -> Vec3<f64> {
+    let mut color = Vec3::default();
+    let mut transmittance = 1.0;
+
+    for &(layer, opacity) in layers {
+        color = color + layer.scale(transmittance * opacity);
+
+        transmittance *= 1.0 - opacity;
+        if transmittance < 1.0 / 255.0 {
+            break;
+        }
+    }
+
+    color + background.scale(transmittance)
 }
 
 trait Light {
     fn pos(&self) -> Vec3<f64>;
     fn intensity(&self, intersection: &Intersection) -> f64;
+    /// Intensity arriving at the given point regardless of the surface
+    /// orientation, used for scattering in participating media.
+    fn irradiance(&self, point: &Vec3<f64>) -> f64;
+    /// Distance from the given point to the light, beyond which objects can't
+    /// cast shadows.
+    fn distance(&self, point: &Vec3<f64>) -> f64;
+
+    /// Color the intensity is spread over, as a float color, see `to_float`.
+    fn color(&self) -> Vec3<f64> {
+        Vec3::new(1.0, 1.0, 1.0)
+    }
+
+    /// Rays from the given point towards the light, the fraction of them that
+    /// is not blocked tells how much of the light is visible.
+    fn shadow_rays(&self, origin: &Vec3<f64>) -> Vec<Ray<f64>> {
+        vec![Ray::new(*origin, self.pos() - *origin, 1.0e-6..self.distance(origin))]
+    }
+}
+
+fn white() -> Rgb<u8> {
+    Rgb([255, 255, 255])
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Deserialize)]
 struct PointLight {
     intensity: f64,
+    #[serde(default = "white", deserialize_with = "deserialize_rgb")]
+    color: Rgb<u8>,
     position: Vec3<f64>,
 }
 
@@ -63,287 +336,1128 @@ impl Light for PointLight {
     }
 
     fn intensity(&self, intersection: &Intersection) -> f64 {
-        let l = self.position - intersection.point;
-        let r = intersection.normal.dot(&l);
-        if r > 0.0 {
-            self.intensity * r / (intersection.normal.len() * l.len())
-        } else {
-            0.0
-        }
+        // Interpolated normals are not necessarily unit.
+        let n = intersection.normal.unit();
+        let l = (self.position - intersection.point).unit();
+
+        self.intensity * n.dot(&l).max(0.0)
     }
-}
 
-struct Scene {
-    lights: Vec<Box<Light + Sync>>,
-    objects: Vec<Model<Box<Geometry + Sync>>>,
+    fn irradiance(&self, _point: &Vec3<f64>) -> f64 {
+        self.intensity
+    }
 
-    depth: u16,
-    background: Rgb<u8>,
+    fn distance(&self, point: &Vec3<f64>) -> f64 {
+        self.position.distance(point)
+    }
+
+    fn color(&self) -> Vec3<f64> {
+        to_float(self.color)
+    }
 }
 
-impl Scene {
-    pub fn new(background: Rgb<u8>) -> Self {
-        Self {
-            lights: Vec::new(),
-            objects: Vec::new(),
-            depth: 2,
-            background,
+/// Point light emitting only into a cone, like a flashlight.
+#[derive(Copy, Clone, Debug, Deserialize)]
+struct SpotLight {
+    position: Vec3<f64>,
+    /// Axis of the cone.
+    direction: Vec3<f64>,
+    intensity: f64,
+    #[serde(default = "white", deserialize_with = "deserialize_rgb")]
+    color: Rgb<u8>,
+    /// Angle from the axis in degrees within which the light is at full
+    /// intensity.
+    inner_angle: f64,
+    /// Angle from the axis in degrees beyond which there is no light.
+    outer_angle: f64,
+}
+
+impl SpotLight {
+    /// Fraction of the intensity reaching the given point, smoothly falling
+    /// off between the inner and outer cones.
+    fn falloff(&self, point: &Vec3<f64>) -> f64 {
+        let cos = (*point - self.position).unit().dot(&self.direction.unit());
+        let inner = self.inner_angle.to_radians().cos();
+        let outer = self.outer_angle.to_radians().cos();
+
+        if cos >= inner {
+            return 1.0;
         }
-    }
 
-    pub fn load<P: AsRef<Path>>(path: &P) -> Result<Self, Box<Error>> {
-        let file = File::open(path)?;
-        let value: serde_json::Value = serde_json::from_reader(file).unwrap();
+        let t = ((cos - outer) / (inner - outer)).max(0.0).min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
 
-        let mut scene = Scene::new(Rgb([30, 30, 30]));
+impl Light for SpotLight {
+    fn pos(&self) -> Vec3<f64> {
+        self.position
+    }
 
-        for model in value["scene"]["models"].as_array().unwrap() {
-            let geometry = &model["geometry"];
-            let transform = &model["transform"];
-            let geometry = match geometry["type"].as_str() {
-                Some("sphere") => {
-                    let mut sphere: Sphere = Deserialize::deserialize(geometry)?;
-                    if !transform.is_null() {
-                        let transformation: Matrix4x4<f64> = Deserialize::deserialize(transform)?;
-                        sphere.transform(&transformation);
-                    }
-                    Box::new(sphere) as Box<Geometry + Sync>
-                }
-                Some("plane") => {
-                    let plane: Plane = Deserialize::deserialize(geometry)?;
-                    Box::new(plane) as Box<Geometry + Sync>
-                }
-                Some("mesh") => {
-                    let mut mesh = Mesh::load(geometry["path"].as_str().unwrap())?;
-                    if !transform.is_null() {
-                        let transformation: Matrix4x4<f64> = Deserialize::deserialize(transform)?;
-                        mesh.transform(&transformation);
-                    }
-                    Box::new(mesh) as Box<Geometry + Sync>
-                }
-                Some(..) => unimplemented!(),
-                None => unimplemented!(),
-            };
+    fn intensity(&self, intersection: &Intersection) -> f64 {
+        let n = intersection.normal.unit();
+        let l = (self.position - intersection.point).unit();
 
-            let material = Deserialize::deserialize(&model["material"])?;
+        self.intensity * n.dot(&l).max(0.0) * self.falloff(&intersection.point)
+    }
 
-            scene.objects.push(Model { geometry, material });
-        }
+    fn irradiance(&self, point: &Vec3<f64>) -> f64 {
+        self.intensity * self.falloff(point)
+    }
 
-        Ok(scene)
+    fn distance(&self, point: &Vec3<f64>) -> f64 {
+        self.position.distance(point)
     }
 
-    pub fn trace(&self, ray: &Ray<f64>) -> Rgb<u8> {
-        self.trace_limited(ray, self.depth)
+    fn color(&self) -> Vec3<f64> {
+        to_float(self.color)
     }
+}
 
-    fn trace_limited(&self, ray: &Ray<f64>, depth: u16) -> Rgb<u8> {
-        self.closest_intersection(ray)
-            .map(|(m, i)| {
-                let intensity = self.lightning(&i);
+fn default_area_samples() -> u32 {
+    16
+}
+
+/// Rectangular light, casting soft shadows.
+#[derive(Copy, Clone, Debug, Deserialize)]
+struct AreaLight {
+    corner: Vec3<f64>,
+    /// Edges of the rectangle, starting at the corner.
+    edges: (Vec3<f64>, Vec3<f64>),
+    intensity: f64,
+    #[serde(default = "white", deserialize_with = "deserialize_rgb")]
+    color: Rgb<u8>,
+    /// Number of shadow rays, the more the smoother penumbra is.
+    #[serde(default = "default_area_samples")]
+    samples: u32,
+}
 
-                let reflective = m.material.reflective;
+impl Light for AreaLight {
+    fn pos(&self) -> Vec3<f64> {
+        self.corner + (self.edges.0 + self.edges.1).scale(0.5)
+    }
 
-                let color = m.material.color.map(|c| {
-                    let color = c as f64 * intensity;
+    fn intensity(&self, intersection: &Intersection) -> f64 {
+        let n = intersection.normal.unit();
+        let l = (self.pos() - intersection.point).unit();
 
-                    if color > 255.0 {
-                        255
-                    } else {
-                        color as u8
-                    }
-                });
+        self.intensity * n.dot(&l).max(0.0)
+    }
 
-                if depth <= 0 || reflective <= 0.0 {
-                    return color;
-                }
+    fn irradiance(&self, _point: &Vec3<f64>) -> f64 {
+        self.intensity
+    }
 
-                let n = i.normal.unit();
-                let d = ray.direction().inverse();
+    fn distance(&self, point: &Vec3<f64>) -> f64 {
+        self.pos().distance(point)
+    }
 
-                let direction = n.scale(2.0 * n.dot(&d)) - d;
-                let ray = Ray::new(i.point, direction, 1.0e-6..1.0e20);
-                let reflected_color = self.trace_limited(&ray, depth - 1);
+    fn color(&self) -> Vec3<f64> {
+        to_float(self.color)
+    }
 
-                let cr = color.map(|c| (c as f64 * (1.0 - reflective)) as u8);
-                let cl = reflected_color.map(|c| (c as f64 * reflective) as u8);
+    fn shadow_rays(&self, origin: &Vec3<f64>) -> Vec<Ray<f64>> {
+        (0..self.samples.max(1) as u64)
+            .map(|idx| {
+                let (u, v) = sampling::halton(idx + 1);
+                let target = self.corner + self.edges.0.scale(u) + self.edges.1.scale(v);
+                let direction = target - *origin;
 
-                Rgb([cr[0] + cl[0], cr[1] + cl[1], cr[2] + cl[2]])
+                Ray::new(*origin, direction, 1.0e-6..direction.len())
             })
-            .unwrap_or(Rgb([
-                self.background[0],
-                self.background[1],
-                self.background[2],
-            ]))
+            .collect()
     }
+}
 
-    fn closest_intersection(&self, ray: &Ray<f64>) -> Option<(&Model<Box<Geometry + Sync>>, Intersection)> {
-        let mut t = f64::INFINITY;
-        let mut closest = None;
+/// Light coming from infinitely far away, like the sun.
+#[derive(Copy, Clone, Debug, Deserialize)]
+struct DirectionalLight {
+    /// Direction the light travels in.
+    direction: Vec3<f64>,
+    intensity: f64,
+    #[serde(default = "white", deserialize_with = "deserialize_rgb")]
+    color: Rgb<u8>,
+}
 
-        for model in &self.objects {
-            if let Some(intersection) = model.geometry.intersection(ray) {
-                if intersection.t < t && ray.contains(intersection.t) {
-                    t = intersection.t;
-                    closest = Some((model, intersection));
-                }
-            }
-        }
+impl DirectionalLight {
+    /// Distance to the point returned by `pos`, far enough to be outside of any
+    /// sensible scene, but close enough for shadow rays.
+    const DISTANCE: f64 = 1.0e10;
+}
 
-        closest
+impl Light for DirectionalLight {
+    fn pos(&self) -> Vec3<f64> {
+        self.direction.unit().scale(-Self::DISTANCE)
     }
 
-    fn lightning(&self, intersection: &Intersection) -> f64 {
-        let mut intensity = 0.0;
-        for light in &self.lights {
-            // Shadows.
-            let direction = light.pos() - intersection.point;
-            let ray = Ray::new(intersection.point, direction, 1.0e-6..1.0e20);
-            if self.closest_intersection(&ray).is_some() {
-                continue;
-            }
+    fn intensity(&self, intersection: &Intersection) -> f64 {
+        let r = intersection.normal.unit().dot(&self.direction.unit().inverse());
+        self.intensity * r.max(0.0)
+    }
 
-            intensity += light.intensity(&intersection);
-        }
+    fn irradiance(&self, _point: &Vec3<f64>) -> f64 {
+        self.intensity
+    }
 
-        intensity
+    fn distance(&self, _point: &Vec3<f64>) -> f64 {
+        1.0e20
+    }
+
+    fn color(&self) -> Vec3<f64> {
+        to_float(self.color)
     }
 }
 
-struct Viewport {
-    width: f64,
-    height: f64,
+/// Parses a model transformation, given either as a raw 4x4 matrix or as a
+/// list of operations like `[{"translate": [0, 1, 0]}, {"rotate_y": 30}]`.
+///
+/// Operations are applied to the model in the listed order, and rotation
+/// angles are in degrees.
+fn parse_transform(value: &serde_json::Value) -> Result<Matrix4x4<f64>, Box<Error>> {
+    let ops = match value {
+        serde_json::Value::Array(rows) if rows.iter().all(|row| row.is_array()) => return Ok(Deserialize::deserialize(value)?),
+        serde_json::Value::Array(ops) => ops.iter().collect(),
+        serde_json::Value::Object(..) => vec![value],
+        _ => return Err("`transform` must be a matrix or a list of operations".into()),
+    };
+
+    let mut transformation = Matrix4x4::identity();
+    for op in ops {
+        let (name, arg) = match op.as_object() {
+            Some(op) if op.len() == 1 => op.iter().next().unwrap(),
+            Some(..) | None => return Err("transform operation must be an object with a single key".into()),
+        };
+
+        let matrix = match name.as_str() {
+            "translate" => Matrix4x4::translation(Deserialize::deserialize(arg)?),
+            "scale" => Matrix4x4::scaling(Deserialize::deserialize(arg)?),
+            "rotate_x" => Matrix4x4::rotation_x(f64::deserialize(arg)?.to_radians()),
+            "rotate_y" => Matrix4x4::rotation_y(f64::deserialize(arg)?.to_radians()),
+            "rotate_z" => Matrix4x4::rotation_z(f64::deserialize(arg)?.to_radians()),
+            name => return Err(format!("unknown transform operation `{}`", name).into()),
+        };
+        transformation = matrix * transformation;
+    }
+
+    Ok(transformation)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let width = 800;
-    let height = 800;
+/// Returns the mesh stored at the given path, loading it only the first time,
+/// optionally with smoothed normals.
+///
+/// Meshes are either stored in a file or embedded as a data URI.
+fn load_mesh(cache: &mut HashMap<(String, bool), Arc<Mesh>>, path: &str, smooth: bool) -> Result<Arc<Mesh>, Box<Error>> {
+    let key = (path.to_string(), smooth);
+    if let Some(mesh) = cache.get(&key) {
+        return Ok(mesh.clone());
+    }
 
-    let viewport = Viewport {
-        width: 1.0,
-        height: 1.0,
+    let mut mesh = if path.starts_with("data:") {
+        Mesh::from_data_uri(path)?
+    } else if path.to_lowercase().ends_with(".stl") {
+        Mesh::load_stl(path)?
+    } else {
+        Mesh::load(path)?
     };
+    if smooth {
+        mesh = mesh.with_smooth_normals();
+    }
+    let mesh = Arc::new(mesh);
+    cache.insert(key, mesh.clone());
 
-    let mut scene = Scene::load(&"scene.json").unwrap();
+    Ok(mesh)
+}
 
-    let lights = 1;
-    for id in 0..lights {
-        let phi = 6.2830 * id as f64 / lights as f64;
-        let radius = 0.5;
-        scene.lights.push(Box::new(PointLight {
-            intensity: 1.0 / lights as f64,
-            position: Vec3::new(10.5, 5.0, -2.0)
-                + Vec3::new(radius * phi.cos(), 0.0, radius * phi.sin()),
-        }));
-    }
+/// Number of frames the interactive renderer summarizes timings over.
+const STATS_INTERVAL: u32 = 25;
 
-    let mut origin = Vec3::new(0.0, 0.0, -2.0);
+/// Largest recursion depth scenes may ask for, as each level multiplies the
+/// number of traced rays.
+const MAX_DEPTH: u16 = 16;
 
-    let ctx = sdl2::init()?;
-    let video = ctx.video()?;
-    let window = video.window("Photon", width, height).position_centered().opengl().build()?;
-    ctx.mouse().show_cursor(false);
+/// Ambient light of scenes that don't set it, whether loaded or built in code.
+const DEFAULT_AMBIENT: f64 = 0.1;
 
-    let mut canvas = window.into_canvas().accelerated().present_vsync().target_texture().build()?;
-    let texture_creator = canvas.texture_creator();
-    canvas.clear();
+/// Camera speed in world units per second.
+const MOVE_SPEED: f64 = 1.5;
 
-    let mut fps = FPSManager::new();
-    fps.set_framerate(25)?;
+/// Keys moving the camera forward, left, back and right.
+const MOVEMENT_KEYS: [Keycode; 4] = [Keycode::W, Keycode::A, Keycode::S, Keycode::D];
 
-    let mut transformation = Matrix4x4::identity();
+/// Returns the camera offset for the given time in seconds with the given keys
+/// held down.
+///
+/// Diagonal movement is as fast as the straight one, and opposite keys cancel
+/// each other out.
+fn movement(pressed: &HashSet<Keycode>, seconds: f64) -> Vec3<f64> {
+    let axis = |positive, negative| match (pressed.contains(&positive), pressed.contains(&negative)) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        (..) => 0.0,
+    };
+    let direction = Vec3::new(axis(Keycode::D, Keycode::A), 0.0, axis(Keycode::W, Keycode::S));
 
-    let mut a = 0.0f64;
-    let mut b = 0.0f64;
+    if direction == Vec3::default() {
+        return direction;
+    }
 
-    let mut events = ctx.event_pump()?;
-    let mut texture = texture_creator.create_texture_streaming(None, width, height)?;
-    'mainloop: loop {
-        const SPEED: f64 = 0.05;
-        for event in events.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Q), ..
-                } => break 'mainloop,
-                Event::KeyDown {
-                    keycode: Some(Keycode::W), ..
-                } => {
-                    origin.z += SPEED;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::A), ..
-                } => {
-                    origin.x -= SPEED;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::S), ..
-                } => {
-                    origin.z -= SPEED;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::D), ..
-                } => {
-                    origin.x += SPEED;
-                }
-                Event::MouseMotion {
-                    xrel, yrel, ..
-                } => {
-                    a += (-yrel as f64) / 100.0;
-                    b += (xrel as f64) / 100.0;
+    direction.unit().scale(MOVE_SPEED * seconds)
+}
 
-                    let rot_x = Matrix4x4::new([
-                        [1.0,  0.0,  0.0, 0.0],
-                        [0.0,  a.cos(),  a.sin(), 0.0],
-                        [0.0, -a.sin(), a.cos(), 0.0],
-                        [0.0,  0.0,  0.0, 1.0],
-                    ]);
-
-                    let rot_y = Matrix4x4::new([
-                        [b.cos(),  0.0, b.sin(), 0.0],
-                        [0.0,         1.0, 0.0,        0.0],
-                        [-b.sin(), 0.0, b.cos(), 0.0],
-                        [0.0,         0.0, 0.0,        1.0],
-                    ]);
-
-                    let rot_z = Matrix4x4::new([
-                        [b.cos(), -b.sin(), 0.0, 0.0],
-                        [b.sin(), b.cos(),  0.0, 0.0],
-                        [0.0,        0.0,         1.0, 0.0],
-                        [0.0,        0.0,         0.0, 1.0],
-                    ]);
-
-                    transformation = rot_y * rot_x;
-                }
-                _event => {}
-            }
+/// Changes the recursion depth by `delta`, keeping it within `0..=MAX_DEPTH`.
+fn adjust_depth(depth: u16, delta: i32) -> u16 {
+    (i32::from(depth) + delta).max(0).min(i32::from(MAX_DEPTH)) as u16
+}
+
+/// Color seen by rays that miss every model.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum Background {
+    Flat(#[serde(deserialize_with = "deserialize_rgb")] Rgb<u8>),
+    /// Vertical gradient, blended by the ray direction from `bottom` for rays
+    /// pointing straight down to `top` for rays pointing straight up.
+    Gradient {
+        #[serde(deserialize_with = "deserialize_rgb")]
+        top: Rgb<u8>,
+        #[serde(deserialize_with = "deserialize_rgb")]
+        bottom: Rgb<u8>,
+    },
+}
+
+impl Background {
+    fn color(&self, direction: &Vec3<f64>) -> Rgb<u8> {
+        match *self {
+            Background::Flat(color) => color,
+            Background::Gradient { top, bottom } => mix(bottom, top, 0.5 * (direction.unit().y + 1.0)),
         }
+    }
+}
 
-        println!("Start drawing ...");
-        let now = Instant::now();
+/// Builds scenes programmatically, checking the settings in `build`.
+///
+/// Scenes are loaded from files outside of tests, see `Scene::from_reader`.
+#[cfg(test)]
+struct SceneBuilder {
+    depth: u16,
+    ambient: f64,
+    background: Background,
+    lights: Vec<Box<Light + Sync>>,
+    objects: Vec<Model<Box<Geometry + Sync>>>,
+}
 
-        texture.with_lock(None, |buf, _pitch| {
-            buf.par_chunks_mut(4).enumerate().for_each(|(n, mut c)| {
-                let x = n % (width as usize);
-                let y = n / (width as usize);
+#[cfg(test)]
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self {
+            depth: 2,
+            ambient: DEFAULT_AMBIENT,
+            background: Background::Flat(Rgb([0, 0, 0])),
+            lights: Vec::new(),
+            objects: Vec::new(),
+        }
+    }
 
-                let sx = x as f64 + width as f64 / -2.0;
-                let sy = height as f64 / 2.0 - y as f64;
+    pub fn with_depth(mut self, depth: u16) -> Self {
+        self.depth = depth;
+        self
+    }
 
-                let vx = sx * viewport.width / width as f64;
-                let vy = sy * viewport.height / height as f64;
-                let vz = 1.0;
+    pub fn with_ambient(mut self, ambient: f64) -> Self {
+        self.ambient = ambient;
+        self
+    }
 
-                let v = Vec3::new(vx, vy, vz);
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
 
-                let mut ray = Ray::new(origin, v, 1.0..1.0e20);
-                ray.transform(&transformation);
+    pub fn add_light(mut self, light: Box<Light + Sync>) -> Self {
+        self.lights.push(light);
+        self
+    }
 
-                let color = scene.trace(&ray);
+    pub fn add_object(mut self, model: Model<Box<Geometry + Sync>>) -> Self {
+        self.objects.push(model);
+        self
+    }
+
+    pub fn build(self) -> Result<Scene, Box<Error>> {
+        if self.depth > MAX_DEPTH {
+            return Err(format!("`depth` must be in 0..={}", MAX_DEPTH).into());
+        }
+        if self.ambient.is_nan() || self.ambient < 0.0 {
+            return Err("`ambient` must not be negative".into());
+        }
+
+        let mut scene = Scene::new(Rgb([0, 0, 0]));
+        scene.depth = self.depth;
+        scene.ambient = self.ambient;
+        scene.background = self.background;
+        scene.lights = self.lights;
+        for object in self.objects {
+            scene.add_model(object);
+        }
+
+        Ok(scene)
+    }
+}
+
+/// Hierarchy over the models with bounding boxes, referring to them by their
+/// indices in `bounded`.
+struct Hierarchy {
+    bvh: Bvh,
+    bounded: Vec<usize>,
+    /// Indices of the models without bounding boxes, e.g. planes, which are
+    /// tested against every ray.
+    unbounded: Vec<usize>,
+}
+
+impl Hierarchy {
+    /// Builds the hierarchy over the models, splitting them into bounded and
+    /// unbounded ones.
+    fn new(objects: &[Model<Box<Geometry + Sync>>]) -> Self {
+        let mut bounds = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+
+        for (idx, model) in objects.iter().enumerate() {
+            match model.geometry.bounding_box() {
+                Some(aabb) => {
+                    bounds.push(aabb);
+                    bounded.push(idx);
+                }
+                None => unbounded.push(idx),
+            }
+        }
+
+        Self {
+            bvh: Bvh::from_bounds(&bounds),
+            bounded,
+            unbounded,
+        }
+    }
+}
+
+struct Scene {
+    lights: Vec<Box<Light + Sync>>,
+    objects: Vec<Model<Box<Geometry + Sync>>>,
+    hierarchy: RwLock<Hierarchy>,
+    /// Set whenever models change, so that the hierarchy is rebuilt once
+    /// before the next ray is traced rather than after every change.
+    dirty: AtomicBool,
+    volumes: Vec<VolumeBox>,
+
+    depth: u16,
+    /// Light reaching every surface regardless of occlusion, so that shadows
+    /// are not pure black.
+    ambient: f64,
+    background: Background,
+    /// Power-law gamma replacing the transfer function of the color space
+    /// when set, e.g. to match a particular display.
+    gamma: Option<f64>,
+    /// Blends overlapping transparent surfaces in depth order as flat layers
+    /// instead of refracting rays through them.
+    composite_transparency: bool,
+    /// Seed of the pseudo-random sample placement, renders with the same seed
+    /// are identical.
+    seed: u64,
+    color_space: ColorSpace,
+    /// Mapping of traced float colors into displayable ones.
+    tone_mapping: ToneMapping,
+    camera: Camera,
+    /// Number of rays looked for the closest hit since the last
+    /// `take_ray_count`.
+    rays: AtomicUsize,
+}
+
+impl Scene {
+    pub fn new(background: Rgb<u8>) -> Self {
+        Self {
+            lights: Vec::new(),
+            objects: Vec::new(),
+            hierarchy: RwLock::new(Hierarchy::new(&[])),
+            dirty: AtomicBool::new(false),
+            volumes: Vec::new(),
+            depth: 2,
+            ambient: DEFAULT_AMBIENT,
+            background: Background::Flat(background),
+            gamma: None,
+            composite_transparency: false,
+            seed: 0,
+            color_space: ColorSpace::default(),
+            tone_mapping: ToneMapping::default(),
+            camera: Camera::default(),
+            rays: AtomicUsize::new(0),
+        }
+    }
+
+    /// Encodes a traced linear color for output, tone mapping it and
+    /// converting it into the scene color space.
+    ///
+    /// This is the only place rendered colors are rounded to bytes, after
+    /// samples and frames are averaged.
+    pub fn encode(&self, color: Vec3<f64>) -> Rgb<u8> {
+        let linear = [
+            self.tone_mapping.apply(color.x),
+            self.tone_mapping.apply(color.y),
+            self.tone_mapping.apply(color.z),
+        ];
+
+        match self.gamma {
+            Some(gamma) => {
+                let c = self.color_space.primaries(linear);
+
+                Rgb([
+                    color::encode_channel(c[0], gamma),
+                    color::encode_channel(c[1], gamma),
+                    color::encode_channel(c[2], gamma),
+                ])
+            }
+            None => self.color_space.encode(linear),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: &P) -> Result<Self, Box<Error>> {
+        let file = File::open(path)?;
+
+        Self::from_reader(file)
+    }
+
+    pub fn from_reader<R: Read>(rd: R) -> Result<Self, Box<Error>> {
+        let value: serde_json::Value = serde_json::from_reader(rd)?;
+        let value = match value.get("scene") {
+            Some(value) if value.is_object() => value,
+            Some(..) | None => return Err("`scene` must be an object".into()),
+        };
+
+        let mut scene = Scene::new(Rgb([30, 30, 30]));
+
+        if let Some(lights) = value["lights"].as_array() {
+            for (idx, light) in lights.iter().enumerate() {
+                let light = match light["type"].as_str() {
+                    Some("point") => {
+                        let light: PointLight = Deserialize::deserialize(light)?;
+                        Box::new(light) as Box<Light + Sync>
+                    }
+                    Some("spot") => {
+                        let light: SpotLight = Deserialize::deserialize(light)?;
+                        Box::new(light) as Box<Light + Sync>
+                    }
+                    Some("area") => {
+                        let light: AreaLight = Deserialize::deserialize(light)?;
+                        Box::new(light) as Box<Light + Sync>
+                    }
+                    Some("directional") => {
+                        let light: DirectionalLight = Deserialize::deserialize(light)?;
+                        Box::new(light) as Box<Light + Sync>
+                    }
+                    Some(ty) => return Err(format!("light {} has unknown type `{}`", idx, ty).into()),
+                    None => return Err(format!("light {} missing `type`", idx).into()),
+                };
+
+                scene.lights.push(light);
+            }
+        }
+
+        let models = value["models"].as_array().ok_or("`scene.models` must be an array")?;
+        // Models referring to the same mesh share its triangles.
+        let mut meshes = HashMap::new();
+        for (idx, model) in models.iter().enumerate() {
+            let geometry = &model["geometry"];
+            let geometry = match geometry["type"].as_str() {
+                Some("sphere") => {
+                    let sphere: Sphere = Deserialize::deserialize(geometry)?;
+                    Box::new(sphere) as Box<Geometry + Sync>
+                }
+                Some("plane") => {
+                    let plane: Plane = Deserialize::deserialize(geometry)?;
+                    Box::new(plane) as Box<Geometry + Sync>
+                }
+                Some("box") => {
+                    let cuboid: Cuboid = Deserialize::deserialize(geometry)?;
+                    Box::new(cuboid) as Box<Geometry + Sync>
+                }
+                Some("cylinder") => {
+                    let cylinder: Cylinder = Deserialize::deserialize(geometry)?;
+                    Box::new(cylinder) as Box<Geometry + Sync>
+                }
+                Some("disk") => {
+                    let disk: Disk = Deserialize::deserialize(geometry)?;
+                    Box::new(disk) as Box<Geometry + Sync>
+                }
+                Some("mesh") => {
+                    let path = geometry["path"]
+                        .as_str()
+                        .ok_or_else(|| format!("model {} missing `geometry.path`", idx))?;
+                    let smooth = geometry["smooth"].as_bool().unwrap_or(false);
+                    let mut mesh = MeshInstance::new(load_mesh(&mut meshes, path, smooth)?);
+                    if !geometry["culling"].is_null() {
+                        let culling: Culling = Deserialize::deserialize(&geometry["culling"])?;
+                        mesh = mesh.with_culling(culling);
+                    }
+                    // Shorthand for closed meshes, whose back faces are never visible.
+                    if let Some(cull) = geometry["cull_backfaces"].as_bool() {
+                        if !geometry["culling"].is_null() {
+                            return Err(format!("model {} has both `culling` and `cull_backfaces`", idx).into());
+                        }
+                        if cull {
+                            mesh = mesh.with_culling(Culling::CullBackface);
+                        }
+                    }
+                    Box::new(mesh) as Box<Geometry + Sync>
+                }
+                Some(ty) => return Err(format!("model {} has unknown geometry type `{}`", idx, ty).into()),
+                None => return Err(format!("model {} missing `geometry.type`", idx).into()),
+            };
+
+            let material = Deserialize::deserialize(&model["material"])?;
+            let mut object = Model::new(geometry, material);
+
+            if let Some(planes) = model["clip"].as_array() {
+                for plane in planes {
+                    // Clip planes are given in model space like the geometry.
+                    object.clip_planes.push(Deserialize::deserialize(plane)?);
+                }
+            }
+
+            let transform = &model["transform"];
+            if !transform.is_null() {
+                let transform = parse_transform(transform)?;
+                // Round shapes can't be stretched into ellipses.
+                let round = match model["geometry"]["type"].as_str() {
+                    Some("sphere") | Some("cylinder") | Some("disk") => true,
+                    Some(..) | None => false,
+                };
+                if round && transform.uniform_scale().is_none() {
+                    return Err(format!("model {} can only be scaled uniformly", idx).into());
+                }
+                object.set_transform(transform);
+            }
+
+            scene.add_model(object);
+        }
+
+        if !value["color_space"].is_null() {
+            scene.color_space = Deserialize::deserialize(&value["color_space"])?;
+        }
+
+        if !value["tone_mapping"].is_null() {
+            scene.tone_mapping = Deserialize::deserialize(&value["tone_mapping"])?;
+        }
+
+        scene.ambient = value["ambient"].as_f64().unwrap_or(DEFAULT_AMBIENT);
+        scene.gamma = value["gamma"].as_f64();
+        scene.composite_transparency = value["composite_transparency"].as_bool().unwrap_or(false);
+        scene.seed = value["seed"].as_u64().unwrap_or(0);
+
+        if let Some(depth) = value.get("depth") {
+            scene.depth = match depth.as_u64() {
+                Some(depth) if depth <= MAX_DEPTH as u64 => depth as u16,
+                Some(..) | None => return Err(format!("`depth` must be an integer in 0..={}", MAX_DEPTH).into()),
+            };
+        }
+
+        if !value["background"].is_null() {
+            scene.background = Deserialize::deserialize(&value["background"])?;
+        }
+
+        if !value["camera"].is_null() {
+            scene.camera = Deserialize::deserialize(&value["camera"])?;
+        }
+
+        if let Some(volumes) = value["volumes"].as_array() {
+            for volume in volumes {
+                let transform = &volume["transform"];
+                let mut volume: VolumeBox = Deserialize::deserialize(volume)?;
+                if !transform.is_null() {
+                    volume.transform(&parse_transform(transform)?);
+                }
+                scene.volumes.push(volume);
+            }
+        }
+
+        Ok(scene)
+    }
+
+    /// Adds the model to the scene, returning its index.
+    pub fn add_model(&mut self, model: Model<Box<Geometry + Sync>>) -> usize {
+        self.objects.push(model);
+        *self.dirty.get_mut() = true;
+        self.objects.len() - 1
+    }
+
+    pub fn remove_model(&mut self, idx: usize) -> Model<Box<Geometry + Sync>> {
+        let model = self.objects.remove(idx);
+        *self.dirty.get_mut() = true;
+        model
+    }
+
+    /// Replaces the transformation of the model at the given index.
+    pub fn set_model_transform(&mut self, idx: usize, transform: Matrix4x4<f64>) {
+        self.objects[idx].set_transform(transform);
+        *self.dirty.get_mut() = true;
+    }
+
+    /// Returns the hierarchy over the models, rebuilding it first if they
+    /// changed since it was last built.
+    fn hierarchy(&self) -> RwLockReadGuard<'_, Hierarchy> {
+        if self.dirty.load(AtomicOrdering::Acquire) {
+            let mut hierarchy = self.hierarchy.write().unwrap();
+            // Another thread may have rebuilt it while this one was waiting.
+            if self.dirty.swap(false, AtomicOrdering::AcqRel) {
+                *hierarchy = Hierarchy::new(&self.objects);
+            }
+        }
+
+        self.hierarchy.read().unwrap()
+    }
+
+    /// Renders a single frame of the scene as seen by the camera, ready to be
+    /// saved.
+    pub fn render(&self, camera: &Camera, width: u32, height: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        self.render_with_progress(camera, width, height, |_| {})
+    }
+
+    /// Renders a single frame like `render`, reporting the progress as the
+    /// number of rows worth of pixels traced so far.
+    pub fn render_with_progress<F>(&self, camera: &Camera, width: u32, height: u32, on_row: F) -> ImageBuffer<Rgb<u8>, Vec<u8>>
+    where
+        F: Fn(u32) + Sync,
+    {
+        let frame = self.render_frame(camera, width, height, 0, on_row);
+
+        ImageBuffer::from_fn(width, height, |x, y| self.encode(frame[(y * width + x) as usize]))
+    }
+
+    /// Renders the given frame into linear float colors row by row, so that
+    /// the window can accumulate frames before encoding them.
+    ///
+    /// See `headless::trace_frame` for how frames differ.
+    pub fn render_frame<F>(&self, camera: &Camera, width: u32, height: u32, frame: u64, on_row: F) -> Vec<Vec3<f64>>
+    where
+        F: Fn(u32) + Sync,
+    {
+        headless::trace_frame(self, camera, width, height, frame, on_row)
+    }
+
+    /// Traces the ray into the tone mapped 8-bit linear color, without the
+    /// transfer function of `encode`.
+    pub fn trace(&self, ray: &Ray<f64>) -> Rgb<u8> {
+        self.quantize(self.trace_hdr(ray))
+    }
+
+    /// Traces the ray into the unbounded float color, see `to_float`.
+    ///
+    /// Colors are carried in float through every bounce, so that contributions
+    /// below the 8-bit step are not lost before they add up.
+    pub fn trace_hdr(&self, ray: &Ray<f64>) -> Vec3<f64> {
+        if self.composite_transparency {
+            self.trace_transparent(ray)
+        } else {
+            self.trace_limited(ray, self.depth)
+        }
+    }
+
+    /// Tone maps the float color into the displayable 8-bit one.
+    fn quantize(&self, color: Vec3<f64>) -> Rgb<u8> {
+        let quantize = |c: f64| (self.tone_mapping.apply(c) * 255.0).round() as u8;
+
+        Rgb([quantize(color.x), quantize(color.y), quantize(color.z)])
+    }
+
+    fn trace_limited(&self, ray: &Ray<f64>, depth: u16) -> Vec3<f64> {
+        let closest = self.closest_intersection(ray);
+        let distance = closest.as_ref().map(|(_, i)| i.t).unwrap_or(f64::INFINITY);
+
+        let color = closest
+            .map(|(m, i)| {
+                let color = self.shade(ray, m, &i, depth);
+                let color = if depth > 0 && m.transparency > 0.0 {
+                    let t = m.transparency;
+                    color.scale(1.0 - t) + self.transmit(ray, m, &i, depth).scale(t)
+                } else {
+                    color
+                };
+
+                color + to_float(m.color_at(&i)).scale(m.emissive)
+            })
+            .unwrap_or_else(|| to_float(self.background.color(ray.direction())));
+
+        self.scatter(ray, distance, color)
+    }
+
+    fn shade(&self, ray: &Ray<f64>, material: &Material, i: &Intersection, depth: u16) -> Vec3<f64> {
+        let intensity = self.lightning(i, &ray.direction().inverse(), material.specular);
+
+        let reflective = material.reflective;
+
+        let color = modulate(to_float(material.color_at(i)), intensity);
+
+        if depth == 0 || reflective <= 0.0 {
+            return color;
+        }
+
+        let n = i.normal.unit();
+        let weight = schlick(ray.direction().dot(&n).abs(), reflective);
+
+        let direction = ray.direction().reflect(&n);
+        let ray = Ray::new(i.point, direction, 1.0e-6..1.0e20);
+        let reflected_color = self.trace_limited(&ray, depth.saturating_sub(1));
+
+        color.scale(1.0 - weight) + reflected_color.scale(weight)
+    }
+
+    /// Traces the light passing through the surface, refracted by the
+    /// material.
+    fn transmit(&self, ray: &Ray<f64>, material: &Material, i: &Intersection, depth: u16) -> Vec3<f64> {
+        let n = i.normal.unit();
+        let direction = match refract(ray.direction(), &n, material.refractive_index) {
+            Some(direction) => direction,
+            // Total internal reflection, nothing passes through.
+            None => ray.direction().reflect(&n),
+        };
+
+        let ray = Ray::new(i.point, direction, 1.0e-6..1.0e20);
+        self.trace_limited(&ray, depth.saturating_sub(1))
+    }
+
+    /// Traces the ray through every transparent surface along it, blending
+    /// them in depth order instead of stopping at the closest one.
+    ///
+    /// Used by `trace_hdr` when `composite_transparency` is set; surfaces are
+    /// blended as flat layers rather than refracting the light through them.
+    pub fn trace_transparent(&self, ray: &Ray<f64>) -> Vec3<f64> {
+        let layers: Vec<(Vec3<f64>, f64)> = self
+            .all_intersections(ray)
+            .into_iter()
+            .map(|(m, i)| (self.shade(ray, m, &i, self.depth), 1.0 - m.transparency))
+            .collect();
+
+        composite(&layers, to_float(self.background.color(ray.direction())))
+    }
+
+    /// Applies participating media the ray passes through before it reaches
+    /// the given distance.
+    fn scatter(&self, ray: &Ray<f64>, distance: f64, color: Vec3<f64>) -> Vec3<f64> {
+        let mut color = color;
+
+        for volume in &self.volumes {
+            let (t0, t1) = match volume.interval(ray) {
+                Some((t0, t1)) if t0 < distance => (t0, t1.min(distance)),
+                Some(..) | None => continue,
+            };
+
+            let transmittance = volume.transmittance(t1 - t0);
+
+            // In-scattered light is estimated once, at the middle of the segment.
+            let point = ray.offset((t0 + t1) / 2.0);
+            let scattered = self.irradiance(&point).scale(volume.albedo() * (1.0 - transmittance));
+
+            color = color.scale(transmittance) + scattered;
+        }
+
+        color
+    }
+
+    fn closest_intersection(&self, ray: &Ray<f64>) -> Option<(&Material, Intersection)> {
+        self.rays.fetch_add(1, AtomicOrdering::Relaxed);
+
+        let mut closest: Option<(&Material, Intersection)> = None;
+
+        let mut visit = |idx: usize| {
+            let (material, intersection) = self.objects[idx].hit(ray)?;
+            if !ray.contains(intersection.t) {
+                return None;
+            }
+            if closest.as_ref().map_or(true, |(_, closest)| intersection.t < closest.t) {
+                closest = Some((material, intersection));
+            }
+
+            Some(intersection.t)
+        };
+
+        let hierarchy = self.hierarchy();
+        for &idx in &hierarchy.unbounded {
+            visit(idx);
+        }
+        hierarchy.bvh.traverse(ray, |idx| visit(hierarchy.bounded[idx]));
+
+        closest
+    }
+
+    /// Returns the number of rays traced since the previous call.
+    pub fn take_ray_count(&self) -> usize {
+        self.rays.swap(0, AtomicOrdering::Relaxed)
+    }
+
+    /// Checks whether the ray hits anything within its range, stopping at the
+    /// first hit found rather than looking for the closest one.
+    fn any_intersection(&self, ray: &Ray<f64>) -> bool {
+        let hits = |idx: usize| self.objects[idx].hit(ray).map_or(false, |(_, i)| ray.contains(i.t));
+
+        let hierarchy = self.hierarchy();
+        if hierarchy.unbounded.iter().any(|&idx| hits(idx)) {
+            return true;
+        }
+
+        let mut found = false;
+        hierarchy.bvh.traverse(ray, |idx| {
+            if hits(hierarchy.bounded[idx]) {
+                found = true;
+                Some(f64::NEG_INFINITY)
+            } else {
+                None
+            }
+        });
+
+        found
+    }
+
+    /// Tests every model, used to verify the hierarchy.
+    #[cfg(test)]
+    fn closest_intersection_linear(&self, ray: &Ray<f64>) -> Option<(&Material, Intersection)> {
+        let mut t = f64::INFINITY;
+        let mut closest = None;
+
+        for model in &self.objects {
+            if let Some((material, intersection)) = model.hit(ray) {
+                if intersection.t < t && ray.contains(intersection.t) {
+                    t = intersection.t;
+                    closest = Some((material, intersection));
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Returns every intersection along the ray ordered by distance.
+    ///
+    /// Each geometry reports only its own closest intersection.
+    fn all_intersections(&self, ray: &Ray<f64>) -> Vec<(&Material, Intersection)> {
+        let mut intersections: Vec<_> = self
+            .objects
+            .iter()
+            .filter_map(|model| model.hit(ray))
+            .filter(|(_, i)| ray.contains(i.t))
+            .collect();
+
+        intersections.sort_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap_or(Ordering::Equal));
+        intersections
+    }
+
+    /// Returns the light reflected by the surface towards the viewer, per
+    /// channel, with `view` pointing from the surface to the viewer.
+    fn lightning(&self, intersection: &Intersection, view: &Vec3<f64>, specular: f64) -> Vec3<f64> {
+        let mut intensity = Vec3::new(self.ambient, self.ambient, self.ambient);
+        for light in &self.lights {
+            let visibility = self.visibility(&**light, intersection);
+            if visibility <= 0.0 {
+                continue;
+            }
+
+            let color = light.color();
+            intensity = intensity + color.scale(light.intensity(&intersection) * visibility);
+
+            if specular >= 0.0 {
+                let n = intersection.normal.unit();
+                let l = (light.pos() - intersection.point).unit();
+                let r = (-l).reflect(&n);
+                let cos = r.dot(view) / view.len();
+                if cos > 0.0 {
+                    intensity = intensity + color.scale(light.irradiance(&intersection.point) * cos.powf(specular) * visibility);
+                }
+            }
+        }
+
+        intensity
+    }
+
+    /// Returns the unoccluded fraction of the light as seen from the surface.
+    fn visibility(&self, light: &Light, intersection: &Intersection) -> f64 {
+        // Shadow rays start slightly off the surface on the side facing the
+        // light, so that the surface doesn't shadow itself due to rounding
+        // errors.
+        let n = intersection.normal.unit();
+        let bias = if n.dot(&(light.pos() - intersection.point)) < 0.0 { -n } else { n };
+        let origin = intersection.point + bias * SHADOW_BIAS;
+
+        let rays = light.shadow_rays(&origin);
+        let unoccluded = rays.iter().filter(|ray| !self.any_intersection(ray)).count();
+
+        unoccluded as f64 / rays.len() as f64
+    }
+
+    fn irradiance(&self, point: &Vec3<f64>) -> Vec3<f64> {
+        let mut irradiance = Vec3::default();
+        for light in &self.lights {
+            let direction = light.pos() - *point;
+            let ray = Ray::new(*point, direction, 1.0e-6..light.distance(point));
+            if self.any_intersection(&ray) {
+                continue;
+            }
+
+            irradiance = irradiance + light.color().scale(light.irradiance(point));
+        }
+
+        irradiance
+    }
+}
+
+/// Converts the BGRA texture contents with rows `pitch` bytes apart into an
+/// RGB image.
+fn bgra_to_image(buf: &[u8], pitch: usize, width: u32, height: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let offset = y as usize * pitch + x as usize * 4;
+        Rgb([buf[offset + 2], buf[offset + 1], buf[offset]])
+    })
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut output = None;
+    let mut manifest = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output = Some(args.next().ok_or("`--output` requires a path")?),
+            "--manifest" => manifest = Some(args.next().ok_or("`--manifest` requires a path")?),
+            arg => return Err(format!("unknown argument `{}`", arg).into()),
+        }
+    }
+
+    if let Some(path) = manifest {
+        return Manifest::load(&path)?.run();
+    }
+
+    let mut scene = Scene::load(&"scene.json")?;
+
+    let mut camera = scene.camera;
+    let (width, height) = (camera.width, camera.height);
+
+    if let Some(path) = output {
+        let image = scene.render_with_progress(&camera, width, height, |rows| eprint!("\rRendered {}/{} rows", rows, height));
+        eprintln!();
+        image.save(&path)?;
+        return Ok(());
+    }
+
+    let (ctx, video) = match sdl2::init().and_then(|ctx| ctx.video().map(|video| (ctx, video))) {
+        Ok((ctx, video)) => (ctx, video),
+        Err(err) => {
+            eprintln!("Failed to initialize SDL video: {}", err);
+            eprintln!("If there is no display available, use `--output <path>` to render into a file instead.");
+            process::exit(1);
+        }
+    };
+    let window = video.window("Photon", width, height).position_centered().opengl().build()?;
+    ctx.mouse().show_cursor(false);
+
+    let mut canvas = window.into_canvas().accelerated().present_vsync().target_texture().build()?;
+    let texture_creator = canvas.texture_creator();
+    canvas.clear();
+
+    let mut fps = FPSManager::new();
+    fps.set_framerate(25)?;
+
+    let mut a = 0.0f64;
+    let mut b = 0.0f64;
+
+    // Frames rendered since the view last changed. Each one is traced with a
+    // different sub-pixel offset and accumulated, so a still image converges
+    // to an anti-aliased one.
+    let mut frame = 0u64;
+    let mut stats = RenderStats::new();
+    let mut accumulation = vec![Vec3::default(); (width * height) as usize];
+
+    // Movement keys held down, the camera moves every frame until they are
+    // released.
+    let mut pressed = HashSet::new();
+    let mut last_frame = Instant::now();
+
+    let mut events = ctx.event_pump()?;
+    let mut texture = texture_creator.create_texture_streaming(None, width, height)?;
+    'mainloop: loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Q), ..
+                } => break 'mainloop,
+                Event::KeyDown {
+                    keycode: Some(Keycode::P), ..
+                } => {
+                    let image = texture.with_lock(None, |buf, pitch| bgra_to_image(buf, pitch, width, height))?;
+                    let path = format!("photon-{}.png", SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+                    image.save(&path)?;
+                    println!("Saved frame to {}", path);
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode), ..
+                } if MOVEMENT_KEYS.contains(&keycode) => {
+                    pressed.insert(keycode);
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode), ..
+                } => {
+                    pressed.remove(&keycode);
+                }
+                // Events are handled between frames, so nothing else borrows
+                // the scene here.
+                Event::KeyDown {
+                    keycode: Some(Keycode::LeftBracket), ..
+                } => {
+                    scene.depth = adjust_depth(scene.depth, -1);
+                    println!("Reflection depth: {}", scene.depth);
+                    frame = 0;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::RightBracket), ..
+                } => {
+                    scene.depth = adjust_depth(scene.depth, 1);
+                    println!("Reflection depth: {}", scene.depth);
+                    frame = 0;
+                }
+                Event::MouseMotion {
+                    xrel, yrel, ..
+                } => {
+                    a += (-yrel as f64) / 100.0;
+                    b += (xrel as f64) / 100.0;
+
+                    let rot_x = Matrix4x4::rotation_x(-a);
+                    let rot_y = Matrix4x4::rotation_y(b);
+
+                    let forward: Vec3<f64> = (&(rot_y * rot_x) * Vec4::direction(Vec3::new(0.0, 0.0, 1.0))).into();
+                    camera.target = camera.position + forward;
+                    frame = 0;
+                }
+                _event => {}
+            }
+        }
+
+        let now = Instant::now();
+        let delta = now - last_frame;
+        last_frame = now;
+
+        let offset = movement(&pressed, delta.as_secs() as f64 + delta.subsec_nanos() as f64 / 1e9);
+        if offset != Vec3::default() {
+            camera.translate(offset);
+            frame = 0;
+        }
+
+        println!("Start drawing ...");
+
+        let colors = scene.render_frame(&camera, width, height, frame, |_| {});
+
+        texture.with_lock(None, |buf, _pitch| {
+            buf.par_chunks_mut(4).zip(accumulation.par_iter_mut()).zip(colors.par_iter()).for_each(|((c, sum), &color)| {
+                *sum = if frame == 0 { Vec3::default() } else { *sum } + color;
+
+                let color = scene.encode(sum.scale(1.0 / (frame + 1) as f64));
 
                 c[0] = color[2];
                 c[1] = color[1];
@@ -352,13 +1466,1322 @@ fn main() -> Result<(), Box<dyn Error>> {
             });
         })?;
 
-        canvas.clear();
-        canvas.copy(&texture, None, None)?;
+        frame += 1;
+
+        canvas.clear();
+        canvas.copy(&texture, None, None)?;
+
+        let elapsed = now.elapsed();
+        println!("Finished, elapsed: {:.3} ms", elapsed.as_millis() as f64);
+        canvas.present();
+
+        stats.record(elapsed, scene.take_ray_count() as u64);
+        if stats.frames() == STATS_INTERVAL {
+            println!("Last {}", stats);
+            stats = RenderStats::new();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn fog_scene(density: f64) -> Scene {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 0.0;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 0.0, -5.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([200, 200, 200])),
+    ));
+    scene.volumes.push(VolumeBox::new(Vec3::new(-1.0, -1.0, 1.0), Vec3::new(1.0, 1.0, 2.0), density, 0.0));
+    scene
+}
+
+#[test]
+fn denser_volume_attenuates_more() {
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    let clear = fog_scene(0.0).trace(&ray);
+    let thin = fog_scene(0.5).trace(&ray);
+    let dense = fog_scene(2.0).trace(&ray);
+
+    assert_eq!(Rgb([200, 200, 200]), clear);
+    assert!(thin[0] < clear[0]);
+    assert!(dense[0] < thin[0]);
+}
+
+#[test]
+fn set_model_transform_moves_hit() {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    let idx = scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([200, 200, 200])),
+    ));
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    let hit = |scene: &Scene| scene.closest_intersection(&ray).map(|(_, i)| i.t);
+
+    assert_eq!(Some(4.0), hit(&scene));
+
+    let translate = |z| Matrix4x4::translation(Vec3::new(0.0, 0.0, z));
+
+    scene.set_model_transform(idx, translate(2.0));
+    assert_eq!(Some(6.0), hit(&scene));
+
+    // Transformations are replaced, not accumulated.
+    scene.set_model_transform(idx, translate(1.0));
+    assert_eq!(Some(5.0), hit(&scene));
+
+    scene.remove_model(idx);
+    assert_eq!(None, hit(&scene));
+}
+
+#[test]
+fn set_model_transform_starts_from_untransformed_geometry() {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    let idx = scene.add_model(Model::new(
+        Box::new(Cuboid::new(Vec3::new(-1.0, -1.0, 4.0), Vec3::new(1.0, 1.0, 6.0))),
+        Material::new(Rgb([200, 200, 200])),
+    ));
+    let bounds = |scene: &Scene| scene.objects[idx].geometry.bounding_box();
+    let expected = bounds(&scene);
+
+    // Bounds of a rotated box enclose it loosely, they must not grow when
+    // the rotation is replaced.
+    for _ in 0..4 {
+        scene.set_model_transform(idx, Matrix4x4::rotation_z(f64::consts::FRAC_PI_4));
+    }
+    scene.set_model_transform(idx, Matrix4x4::identity());
+    assert_eq!(expected, bounds(&scene));
+
+    // Replacing a singular transformation doesn't need to invert it.
+    scene.set_model_transform(idx, Matrix4x4::new([[0.0; 4]; 4]));
+    scene.set_model_transform(idx, Matrix4x4::identity());
+    assert_eq!(expected, bounds(&scene));
+}
+
+#[test]
+fn composite_matches_back_to_front_blend() {
+    let background = Vec3::new(0.1, 0.2, 0.3);
+    let layers = [
+        (Vec3::new(1.0, 0.0, 0.0), 0.5),
+        (Vec3::new(0.0, 1.0, 0.0), 0.25),
+        (Vec3::new(0.0, 0.0, 1.0), 0.75),
+    ];
+
+    let mut expected = background;
+    for &(layer, opacity) in layers.iter().rev() {
+        expected = layer.scale(opacity) + expected.scale(1.0 - opacity);
+    }
+
+    assert!(composite(&layers, background).distance(&expected) < 1e-12);
+}
+
+#[test]
+fn trace_transparent_blends_stacked_planes() {
+    let mut scene = Scene::new(Rgb([160, 80, 48]));
+    // Without lights, full ambient light shows the plain plane colors.
+    scene.ambient = 1.0;
+    let planes = [
+        (3.0, Rgb([0, 0, 255]), 0.5),
+        (1.0, Rgb([255, 0, 0]), 0.5),
+        (2.0, Rgb([0, 255, 0]), 0.25),
+    ];
+    for &(z, color, transparency) in &planes {
+        scene.add_model(Model::new(
+            Box::new(Plane::new(Vec3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, -1.0))),
+            Material {
+                transparency,
+                ..Material::new(color)
+            },
+        ));
+    }
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    let hits: Vec<f64> = scene.all_intersections(&ray).iter().map(|(_, i)| i.t).collect();
+    assert_eq!(vec![1.0, 2.0, 3.0], hits);
+
+    // Blends the farthest plane first, regardless of the order of the models.
+    let mut expected = to_float(Rgb([160, 80, 48]));
+    for &idx in &[0, 2, 1] {
+        let (_, color, transparency) = planes[idx];
+        expected = to_float(color).scale(1.0 - transparency) + expected.scale(transparency);
+    }
+
+    assert!(scene.trace_transparent(&ray).distance(&expected) < 1e-12);
+
+    scene.composite_transparency = true;
+    assert!(scene.trace_hdr(&ray).distance(&expected) < 1e-12);
+}
+
+#[test]
+fn load_scene_composite_transparency() {
+    let scene = Scene::from_reader(r#"{"scene": {"models": []}}"#.as_bytes()).unwrap();
+    assert!(!scene.composite_transparency);
+
+    let scene = Scene::from_reader(r#"{"scene": {"models": [], "composite_transparency": true}}"#.as_bytes()).unwrap();
+    assert!(scene.composite_transparency);
+}
+
+#[test]
+fn transparent_planes_let_background_through() {
+    let mut scene = Scene::new(Rgb([160, 80, 48]));
+    scene.ambient = 0.0;
+    // Every plane needs a bounce to pass the light behind it through.
+    scene.depth = 3;
+    for &(z, transparency) in &[(3.0, 0.5), (1.0, 0.5), (2.0, 0.25)] {
+        scene.add_model(Model::new(
+            Box::new(Plane::new(Vec3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, -1.0))),
+            Material {
+                transparency,
+                ..Material::new(Rgb([255, 255, 255]))
+            },
+        ));
+    }
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    // Without lights every plane is black, so only the background shows
+    // through the accumulated transparency: 0.5 * 0.25 * 0.5 = 1/16.
+    assert_eq!(Rgb([10, 5, 3]), scene.trace(&ray));
+}
+
+#[test]
+fn clip_plane_hides_sphere_half() {
+    let mut model: Model<Box<Geometry + Sync>> =
+        Model::new(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)), Material::new(Rgb([255, 0, 0])));
+    model.clip_planes.push(ClipPlane::new(Vec3::new(0.0, 0.0, 4.5), Vec3::new(0.0, 0.0, -1.0)));
 
-        let elapsed = now.elapsed();
-        println!("Finished, elapsed: {:.3} ms", elapsed.as_millis() as f64);
-        canvas.present();
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.add_model(model);
+
+    // The front of the sphere is still visible.
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    assert_eq!(Some(4.0), scene.closest_intersection(&ray).map(|(_, i)| i.t));
+
+    // Everything beyond the plane is gone.
+    let ray = Ray::new(Vec3::new(-5.0, 0.0, 5.0), Vec3::new(1.0, 0.0, 0.0), 1.0e-6..1.0e20);
+    assert!(scene.closest_intersection(&ray).is_none());
+}
+
+#[test]
+fn model_hit_returns_material() {
+    let model: Model<Box<Geometry + Sync>> = Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([1, 2, 3])),
+    );
+
+    let (material, intersection) = model.hit(&Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20)).unwrap();
+    assert_eq!(Rgb([1, 2, 3]), material.color);
+    assert_eq!(4.0, intersection.t);
+
+    assert!(model.hit(&Ray::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0), 1.0e-6..1.0e20)).is_none());
+}
+
+#[test]
+fn clip_plane_caps_solid() {
+    let cap = Material::new(Rgb([0, 0, 255]));
+    let cube = geometry::cube(Vec3::new(-1.0, -1.0, 4.0), Vec3::new(1.0, 1.0, 6.0));
+    let plane = ClipPlane::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    let mut model: Model<Box<Geometry + Sync>> = Model::new(Box::new(cube.clone()), Material::new(Rgb([255, 0, 0])));
+    model.clip_planes.push(plane.clone());
+
+    // Without a cap the ray looks into the cube and sees its back wall.
+    let (material, intersection) = model.hit(&ray).unwrap();
+    assert_eq!(Rgb([255, 0, 0]), material.color);
+    assert_eq!(6.0, intersection.t);
+
+    let mut model: Model<Box<Geometry + Sync>> = Model::new(Box::new(cube), Material::new(Rgb([255, 0, 0])));
+    model.clip_planes.push(plane.with_cap(cap));
+
+    let (material, intersection) = model.hit(&ray).unwrap();
+    assert_eq!(Rgb([0, 0, 255]), material.color);
+    assert_eq!(5.0, intersection.t);
+    assert_eq!(Vec3::new(0.0, 0.0, 5.0), intersection.point);
+    assert_eq!(Vec3::new(0.0, 0.0, -1.0), intersection.normal);
+}
+
+#[test]
+fn load_scene_with_embedded_mesh() {
+    let obj = "v 0 0 5\nv 1 0 5\nv 0 1 5\nvn 0 0 -1\nf 1//1 2//1 3//1\n";
+    let json = format!(
+        r#"{{"scene": {{"models": [{{"geometry": {{"type": "mesh", "path": "data:model/obj;base64,{}"}}, "material": {{"color": [255, 0, 0], "reflective": 0}}}}]}}}}"#,
+        base64::encode(obj)
+    );
+
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+    let ray = Ray::new(Vec3::new(0.25, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    assert_eq!(1, scene.objects.len());
+    assert_eq!(Some(5.0), scene.closest_intersection(&ray).map(|(_, i)| i.t));
+}
+
+#[test]
+fn load_mesh_with_backface_culling() {
+    let load = |geometry: &str| {
+        let json = format!(
+            r#"{{"scene": {{"models": [{{"geometry": {{"type": "mesh", "path": "data:model/obj;base64,{}"{}}}, "material": {{"color": [255, 0, 0], "reflective": 0}}}}]}}}}"#,
+            base64::encode("v 0 0 5\nv 1 0 5\nv 0 1 5\nf 1 2 3\n"),
+            geometry
+        );
+        Scene::from_reader(json.as_bytes())
+    };
+    // The triangle is counter-clockwise as seen from the positive z side.
+    let front = Ray::new(Vec3::new(0.25, 0.25, 10.0), Vec3::new(0.0, 0.0, -1.0), 1.0e-6..1.0e20);
+    let back = Ray::new(Vec3::new(0.25, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    let scene = load(r#", "cull_backfaces": false"#).unwrap();
+    assert!(scene.closest_intersection(&back).is_some());
+
+    let scene = load(r#", "cull_backfaces": true"#).unwrap();
+    assert!(scene.closest_intersection(&front).is_some());
+    assert!(scene.closest_intersection(&back).is_none());
+
+    let err = load(r#", "cull_backfaces": true, "culling": "two_sided""#).err().unwrap();
+    assert_eq!("model 0 has both `culling` and `cull_backfaces`", err.to_string());
+}
+
+#[test]
+fn load_mesh_once() {
+    let path = format!("data:model/obj;base64,{}", base64::encode("v 0 0 5\nv 1 0 5\nv 0 1 5\nf 1 2 3\n"));
+    let mut cache = HashMap::new();
+
+    let a = load_mesh(&mut cache, &path, false).unwrap();
+    let b = load_mesh(&mut cache, &path, false).unwrap();
+
+    assert!(Arc::ptr_eq(&a, &b));
+    assert_eq!(1, cache.len());
+
+    // Smoothing changes the normals, so the mesh can't be shared.
+    let c = load_mesh(&mut cache, &path, true).unwrap();
+    assert!(!Arc::ptr_eq(&a, &c));
+}
+
+#[test]
+fn load_scene_with_mesh_instances() {
+    let obj = "v 0 0 5\nv 1 0 5\nv 0 1 5\nvn 0 0 -1\nf 1//1 2//1 3//1\n";
+    let model = |x: f64| {
+        format!(
+            r#"{{"geometry": {{"type": "mesh", "path": "data:model/obj;base64,{}"}}, "material": {{"color": [255, 0, 0], "reflective": 0}}, "transform": [{{"translate": [{}, 0, 0]}}]}}"#,
+            base64::encode(obj),
+            x
+        )
+    };
+    let json = format!(r#"{{"scene": {{"models": [{}, {}]}}}}"#, model(0.0), model(10.0));
+
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+    let hit = |x: f64| {
+        let ray = Ray::new(Vec3::new(x, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+        scene.closest_intersection(&ray).map(|(_, i)| i.t)
+    };
+
+    // Transformations of one instance don't move the other.
+    assert_eq!(Some(5.0), hit(0.25));
+    assert_eq!(Some(5.0), hit(10.25));
+    assert_eq!(None, hit(5.25));
+}
+
+#[test]
+fn hierarchy_matches_linear_intersection() {
+    // Deterministic pseudo-random numbers in `[0, 1)`.
+    let mut seed = 42u64;
+    let mut random = move || {
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 11) as f64 / (1u64 << 53) as f64
+    };
+
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    for _ in 0..100 {
+        let center = Vec3::new(random() * 20.0 - 10.0, random() * 20.0 - 10.0, random() * 20.0 + 5.0);
+        scene.add_model(Model::new(Box::new(Sphere::new(center, random() + 0.1)), Material::new(Rgb([255, 255, 255]))));
     }
+    scene.add_model(Model::new(
+        Box::new(Plane::new(Vec3::new(0.0, 0.0, 30.0), Vec3::new(0.0, 0.0, -1.0))),
+        Material::new(Rgb([255, 255, 255])),
+    ));
+
+    for _ in 0..1000 {
+        let direction = Vec3::new(random() - 0.5, random() - 0.5, 1.0);
+        let ray = Ray::new(Vec3::default(), direction, 1.0e-6..random() * 40.0);
+
+        let expected = scene.closest_intersection_linear(&ray).map(|(_, i)| i.t);
+        assert_eq!(expected, scene.closest_intersection(&ray).map(|(_, i)| i.t));
+        assert_eq!(expected.is_some(), scene.any_intersection(&ray));
+    }
+}
 
-    Ok(())
+/// Geometry counting how many times it was intersected and asked for its
+/// bounding box.
+#[cfg(test)]
+#[derive(Clone)]
+struct Counted<G> {
+    geometry: G,
+    calls: Arc<std::sync::atomic::AtomicUsize>,
+    bounds: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+#[cfg(test)]
+impl<G: Geometry + Clone + Sync + 'static> Geometry for Counted<G> {
+    fn intersection(&self, ray: &Ray<f64>) -> Option<Intersection> {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.geometry.intersection(ray)
+    }
+
+    fn bounding_box(&self) -> Option<geometry::Aabb> {
+        self.bounds.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.geometry.bounding_box()
+    }
+}
+
+#[cfg(test)]
+impl<G: Geometry> Transform<f64> for Counted<G> {
+    fn transform(&mut self, transformation: &Matrix4x4<f64>) {
+        self.geometry.transform(transformation);
+    }
+}
+
+#[test]
+fn closest_intersection_counts_rays() {
+    let scene = SceneBuilder::new()
+        .add_object(Model::new(
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+            Material::new(Rgb([255, 255, 255])),
+        ))
+        .build()
+        .unwrap();
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    scene.trace(&ray);
+    scene.trace(&ray);
+
+    assert_eq!(2, scene.take_ray_count());
+    assert_eq!(0, scene.take_ray_count());
+}
+
+#[test]
+fn any_intersection_stops_at_first_hit() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    for &z in &[5.0, 10.0] {
+        scene.add_model(Model::new(
+            Box::new(Counted {
+                geometry: Sphere::new(Vec3::new(0.0, 0.0, z), 1.0),
+                calls: calls.clone(),
+                bounds: Arc::new(AtomicUsize::new(0)),
+            }),
+            Material::new(Rgb([255, 255, 255])),
+        ));
+    }
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    assert!(scene.any_intersection(&ray));
+    assert_eq!(1, calls.load(Ordering::SeqCst));
+
+    calls.store(0, Ordering::SeqCst);
+    assert!(scene.closest_intersection(&ray).is_some());
+    assert_eq!(2, calls.load(Ordering::SeqCst));
+}
+
+#[test]
+fn hierarchy_is_rebuilt_once_after_edits() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let bounds = Arc::new(AtomicUsize::new(0));
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.add_model(Model::new(
+        Box::new(Counted {
+            geometry: Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0),
+            calls: Arc::new(AtomicUsize::new(0)),
+            bounds: bounds.clone(),
+        }),
+        Material::new(Rgb([255, 255, 255])),
+    ));
+    let idx = scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 10.0), 1.0)),
+        Material::new(Rgb([255, 255, 255])),
+    ));
+    scene.set_model_transform(idx, Matrix4x4::translation(Vec3::new(1.0, 0.0, 0.0)));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 20.0), 1.0)),
+        Material::new(Rgb([255, 255, 255])),
+    ));
+    scene.remove_model(idx);
+
+    assert_eq!(0, bounds.load(Ordering::SeqCst));
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    scene.trace(&ray);
+    scene.trace(&ray);
+    assert_eq!(1, bounds.load(Ordering::SeqCst));
+
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 30.0), 1.0)),
+        Material::new(Rgb([255, 255, 255])),
+    ));
+    scene.trace(&ray);
+    assert_eq!(2, bounds.load(Ordering::SeqCst));
+}
+
+#[test]
+fn render_single_sphere() {
+    let mut scene = Scene::new(Rgb([10, 20, 30]));
+    scene.gamma = Some(1.0);
+    scene.ambient = 0.0;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 0.0, -5.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([0, 200, 0])),
+    ));
+    let camera = Camera::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 60.0);
+
+    let image = scene.render(&camera, 9, 9);
+
+    // The center of the sphere faces the light head-on.
+    assert_eq!(Rgb([0, 200, 0]), *image.get_pixel(4, 4));
+    assert_eq!(Rgb([10, 20, 30]), *image.get_pixel(0, 0));
+}
+
+#[test]
+fn emissive_sphere_glows_without_lights() {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material {
+            emissive: 0.5,
+            ..Material::new(Rgb([200, 100, 50]))
+        },
+    ));
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    let color = scene.trace(&ray);
+
+    for (&actual, &emission) in color.data.iter().zip(&[100, 50, 25]) {
+        assert!(actual >= emission, "{:?}", color);
+    }
+}
+
+#[test]
+fn movement_integrates_over_time() {
+    let keys = |keys: &[Keycode]| keys.iter().cloned().collect::<HashSet<_>>();
+    let forward = keys(&[Keycode::W]);
+
+    let one = movement(&forward, 0.02);
+    let two = movement(&forward, 0.02) + movement(&forward, 0.02);
+    assert!(one.z > 0.0);
+    assert!((two.z - 2.0 * one.z).abs() < 1e-12);
+
+    let diagonal = movement(&keys(&[Keycode::W, Keycode::D]), 0.02);
+    assert!((diagonal.len() - one.len()).abs() < 1e-12);
+    assert_eq!(Vec3::default(), movement(&keys(&[Keycode::W, Keycode::S]), 0.02));
+}
+
+#[test]
+fn adjust_depth_stays_in_range() {
+    assert_eq!(3, adjust_depth(2, 1));
+    assert_eq!(1, adjust_depth(2, -1));
+    assert_eq!(0, adjust_depth(0, -1));
+    assert_eq!(MAX_DEPTH, adjust_depth(MAX_DEPTH, 1));
+    assert_eq!(MAX_DEPTH, adjust_depth(MAX_DEPTH + 5, 0));
+}
+
+#[test]
+fn build_scene() {
+    let scene = SceneBuilder::new()
+        .with_depth(4)
+        .with_ambient(0.2)
+        .with_background(Background::Flat(Rgb([1, 2, 3])))
+        .add_light(Box::new(PointLight {
+            intensity: 1.0,
+            color: Rgb([255, 255, 255]),
+            position: Vec3::default(),
+        }))
+        .add_object(Model::new(
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+            Material::new(Rgb([255, 255, 255])),
+        ))
+        .build()
+        .unwrap();
+
+    assert_eq!(4, scene.depth);
+    assert_eq!(0.2, scene.ambient);
+    assert_eq!(1, scene.lights.len());
+    assert_eq!(1, scene.objects.len());
+
+    let miss = Ray::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0), 1.0e-6..1.0e20);
+    let hit = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    assert_eq!(Rgb([1, 2, 3]), scene.trace(&miss));
+    assert!(scene.closest_intersection(&hit).is_some());
+}
+
+#[test]
+fn build_invalid_scene() {
+    let error = |builder: SceneBuilder| builder.build().err().unwrap().to_string();
+
+    assert_eq!("`depth` must be in 0..=16", error(SceneBuilder::new().with_depth(17)));
+    assert_eq!("`ambient` must not be negative", error(SceneBuilder::new().with_ambient(-0.1)));
+    assert_eq!("`ambient` must not be negative", error(SceneBuilder::new().with_ambient(f64::NAN)));
+}
+
+#[test]
+fn deserialize_hex_color() {
+    let color = |color: &str| {
+        let json = format!(r#"{{"color": {}, "reflective": 0}}"#, color);
+        serde_json::from_str::<Material>(&json).map(|material| material.color)
+    };
+
+    assert_eq!(Rgb([255, 136, 0]), color("[255, 136, 0]").unwrap());
+    assert_eq!(Rgb([255, 136, 0]), color(r##""#ff8800""##).unwrap());
+    assert_eq!(Rgb([255, 136, 0]), color(r##""#f80""##).unwrap());
+
+    let err = color(r##""#xyz""##).err().unwrap();
+    assert!(err.to_string().starts_with("invalid color `#xyz`"), "{}", err);
+}
+
+#[test]
+fn mix_does_not_overflow() {
+    for &t in &[0.1, 0.3, 0.5, 0.7, 0.75, 0.9] {
+        assert_eq!(Rgb([255, 255, 255]), mix(Rgb([255, 255, 255]), Rgb([255, 255, 255]), t));
+    }
+
+    assert_eq!(Rgb([150, 25, 0]), mix(Rgb([200, 0, 0]), Rgb([0, 100, 0]), 0.25));
+}
+
+#[test]
+fn specular_highlight() {
+    let scene = |specular| {
+        let mut scene = Scene::new(Rgb([0, 0, 0]));
+        scene.ambient = 0.0;
+        scene.lights.push(Box::new(PointLight {
+            intensity: 0.5,
+            color: Rgb([255, 255, 255]),
+            position: Vec3::default(),
+        }));
+        scene.add_model(Model::new(
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+            Material {
+                specular,
+                ..Material::new(Rgb([100, 100, 100]))
+            },
+        ));
+        scene
+    };
+
+    let center = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    let side = Ray::new(Vec3::default(), Vec3::new(0.15, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    // Diffuse only.
+    assert_eq!(Rgb([50, 50, 50]), scene(-1.0).trace(&center));
+
+    // The highlight faces the light, fading away from it.
+    let matte = scene(-1.0).trace(&side);
+    let glossy = scene(50.0);
+    assert_eq!(Rgb([100, 100, 100]), glossy.trace(&center));
+    assert!(glossy.trace(&side)[0] < 100);
+    assert!(glossy.trace(&side)[0] >= matte[0]);
+}
+
+#[test]
+fn load_scene_with_camera() {
+    let json = r#"{"scene": {"models": [], "camera": {
+        "position": [1, 2, 3], "target": [0, 0, 10], "up": [0, 0, 1], "fov": 90, "width": 640, "height": 480
+    }}}"#;
+
+    let camera = Scene::from_reader(json.as_bytes()).unwrap().camera;
+
+    assert_eq!(Vec3::new(1.0, 2.0, 3.0), camera.position);
+    assert_eq!(Vec3::new(0.0, 0.0, 10.0), camera.target);
+    assert_eq!(Vec3::new(0.0, 0.0, 1.0), camera.up);
+    assert_eq!(90.0, camera.fov);
+    assert_eq!((640, 480), (camera.width, camera.height));
+}
+
+#[test]
+fn load_scene_with_orthographic_camera() {
+    use crate::camera::Projection;
+
+    let json = r#"{"scene": {"models": [], "camera": {"projection": "orthographic", "ortho_height": 3.0}}}"#;
+
+    let camera = Scene::from_reader(json.as_bytes()).unwrap().camera;
+
+    assert_eq!(Projection::Orthographic, camera.projection);
+    assert_eq!(3.0, camera.ortho_height);
+}
+
+#[test]
+fn load_scene_without_camera() {
+    let json = r#"{"scene": {"models": []}}"#;
+
+    let camera = Scene::from_reader(json.as_bytes()).unwrap().camera;
+
+    assert_eq!(Camera::default().position, camera.position);
+    assert_eq!((800, 800), (camera.width, camera.height));
+}
+
+#[test]
+fn directional_light_intensity() {
+    let light = DirectionalLight {
+        direction: Vec3::new(0.0, -2.0, 0.0),
+        intensity: 0.8,
+        color: Rgb([255, 255, 255]),
+    };
+    let facing = Intersection::new(1.0, Vec3::default(), Vec3::new(0.0, 1.0, 0.0));
+    let back = Intersection::new(1.0, Vec3::default(), Vec3::new(0.0, -1.0, 0.0));
+
+    assert_eq!(0.8, light.intensity(&facing));
+    assert_eq!(0.0, light.intensity(&back));
+    assert!(light.pos().y > 1.0e9);
+}
+
+#[test]
+fn ambient_light_in_shadow() {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 0.2;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 10.0, -6.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([200, 100, 50])),
+    ));
+    // Sits halfway between the light and the visible point of the sphere.
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 5.0, -1.0), 1.0)),
+        Material::new(Rgb([0, 0, 0])),
+    ));
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    assert_eq!(Rgb([40, 20, 10]), scene.trace(&ray));
+}
+
+#[test]
+fn load_scene_background() {
+    let trace = |json: &str, y: f64| {
+        let scene = Scene::from_reader(json.as_bytes()).unwrap();
+        scene.trace(&Ray::new(Vec3::default(), Vec3::new(0.0, y, 1.0), 1.0e-6..1.0e20))
+    };
+
+    let flat = r#"{"scene": {"models": [], "background": [1, 2, 3]}}"#;
+    assert_eq!(Rgb([1, 2, 3]), trace(flat, -1.0e9));
+    assert_eq!(Rgb([1, 2, 3]), trace(flat, 1.0e9));
+
+    let gradient = r#"{"scene": {"models": [], "background": {"top": [0, 100, 200], "bottom": [200, 100, 0]}}}"#;
+    assert_eq!(Rgb([200, 100, 0]), trace(gradient, -1.0e9));
+    assert_eq!(Rgb([0, 100, 200]), trace(gradient, 1.0e9));
+    assert_eq!(Rgb([100, 100, 100]), trace(gradient, 0.0));
+}
+
+#[test]
+fn load_scene_depth() {
+    let load = |json: &str| Scene::from_reader(json.as_bytes());
+
+    assert_eq!(5, load(r#"{"scene": {"models": [], "depth": 5}}"#).unwrap().depth);
+    assert_eq!(2, load(r#"{"scene": {"models": []}}"#).unwrap().depth);
+
+    for json in &[r#"{"scene": {"models": [], "depth": 17}}"#, r#"{"scene": {"models": [], "depth": -1}}"#] {
+        assert_eq!("`depth` must be an integer in 0..=16", load(json).err().unwrap().to_string());
+    }
+}
+
+#[test]
+fn load_scene_gamma() {
+    let scene = Scene::from_reader(r#"{"scene": {"models": []}}"#.as_bytes()).unwrap();
+    assert_eq!(None, scene.gamma);
+    assert_eq!(scene.gamma, Scene::new(Rgb([0, 0, 0])).gamma);
+
+    let scene = Scene::from_reader(r#"{"scene": {"models": [], "gamma": 2.2}}"#.as_bytes()).unwrap();
+    assert_eq!(Rgb([186, 186, 186]), scene.encode(Vec3::new(0.5, 0.5, 0.5)));
+    // Out of range channels are clamped.
+    assert_eq!(Rgb([0, 255, 255]), scene.encode(Vec3::new(-0.5, 1.0, 1.5)));
+
+    let scene = Scene::from_reader(r#"{"scene": {"models": [], "gamma": 1.0}}"#.as_bytes()).unwrap();
+    assert_eq!(Rgb([128, 128, 128]), scene.encode(Vec3::new(0.5, 0.5, 0.5)));
+}
+
+#[test]
+fn encode_into_scene_color_space() {
+    let load = |json: &str| Scene::from_reader(json.as_bytes()).unwrap();
+    let midtone = Vec3::new(0.5, 0.5, 0.5);
+
+    let srgb = load(r#"{"scene": {"models": []}}"#);
+    assert_eq!(ColorSpace::Srgb, srgb.color_space);
+    assert_eq!(Rgb([188, 188, 188]), srgb.encode(midtone));
+
+    let linear = load(r#"{"scene": {"models": [], "color_space": "linear"}}"#);
+    assert_eq!(ColorSpace::Linear, linear.color_space);
+    assert_eq!(Rgb([128, 128, 128]), linear.encode(midtone));
+    assert_eq!(Rgb([0, 255, 255]), linear.encode(Vec3::new(0.0, 1.0, 1.0)));
+}
+
+#[test]
+fn encoding_float_colors_keeps_dark_gradations() {
+    // A dim floor lit at varying angles, spanning a few 8-bit steps only,
+    // which the gamma then stretches apart.
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 0.0;
+    scene.gamma = Some(2.2);
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 1.0, 1.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0))),
+        Material::new(Rgb([16, 16, 16])),
+    ));
+
+    let rays: Vec<_> = (-200..200)
+        .map(|x| Ray::new(Vec3::default(), Vec3::new(x as f64 / 20.0, -1.0, 1.0), 1.0e-6..1.0e20))
+        .collect();
+    let encoded: HashSet<u8> = rays.iter().map(|ray| scene.encode(scene.trace_hdr(ray))[0]).collect();
+    // Previously every sample was rounded to bytes before the gamma.
+    let rounded: HashSet<u8> = rays
+        .iter()
+        .map(|ray| color::encode_channel(scene.trace(ray)[0] as f64 / 255.0, 2.2))
+        .collect();
+
+    assert!(encoded.len() > 2 * rounded.len(), "{} vs {}", encoded.len(), rounded.len());
+}
+
+#[test]
+fn reinhard_tone_mapping_compresses_bright_lights() {
+    let trace = |json: &str, intensity| {
+        let mut scene = Scene::from_reader(json.as_bytes()).unwrap();
+        scene.ambient = 0.0;
+        scene.lights.push(Box::new(PointLight {
+            intensity,
+            color: Rgb([255, 255, 255]),
+            position: Vec3::default(),
+        }));
+        scene.add_model(Model::new(
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+            Material::new(Rgb([200, 100, 0])),
+        ));
+
+        scene.trace(&Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20))
+    };
+    let clamp = r#"{"scene": {"models": []}}"#;
+    let reinhard = r#"{"scene": {"models": [], "tone_mapping": "reinhard"}}"#;
+
+    // Clamping saturates both lights alike, losing the difference.
+    assert_eq!(Rgb([255, 255, 0]), trace(clamp, 4.0));
+    assert_eq!(trace(clamp, 4.0), trace(clamp, 8.0));
+
+    let dim = trace(reinhard, 4.0);
+    let bright = trace(reinhard, 8.0);
+    assert!(dim[0] < bright[0] && bright[0] < 255, "{:?} {:?}", dim, bright);
+    assert!(dim[1] < dim[0]);
+}
+
+#[test]
+fn load_scene_with_sphere_and_plane() {
+    let json = r#"{"scene": {"models": [
+        {"geometry": {"type": "sphere", "center": [0, 0, 5], "radius": 1}, "material": {"color": [255, 0, 0], "reflective": 0}},
+        {"geometry": {"type": "plane", "point": [0, -1, 0], "normal": [0, 1, 0]}, "material": {"color": [0, 255, 0], "reflective": 0}}
+    ]}}"#;
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    let hit = |direction| {
+        let ray = Ray::new(Vec3::default(), direction, 1.0e-6..1.0e20);
+        scene.closest_intersection(&ray).map(|(m, i)| (m.color, i.point))
+    };
+
+    assert_eq!(Some((Rgb([255, 0, 0]), Vec3::new(0.0, 0.0, 4.0))), hit(Vec3::new(0.0, 0.0, 1.0)));
+    assert_eq!(Some((Rgb([0, 255, 0]), Vec3::new(0.0, -1.0, 0.0))), hit(Vec3::new(0.0, -1.0, 0.0)));
+}
+
+#[test]
+fn load_scene_transforms_plane() {
+    // The floor is moved down and then turned into a wall facing the origin.
+    let json = r#"{"scene": {"models": [
+        {"geometry": {"type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0]}, "material": {"color": [0, 255, 0], "reflective": 0},
+         "transform": [{"translate": [0, -2, 0]}, {"rotate_z": 90}]}
+    ]}}"#;
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(1.0, 0.0, 0.0), 1.0e-6..1.0e20);
+    let (_, i) = scene.closest_intersection(&ray).unwrap();
+
+    assert!(i.point.distance(&Vec3::new(2.0, 0.0, 0.0)) < 1e-9, "{:?}", i.point);
+    assert!(i.normal.distance(&Vec3::new(-1.0, 0.0, 0.0)) < 1e-9, "{:?}", i.normal);
+}
+
+#[test]
+fn load_scene_scales_round_shapes() {
+    let load = |geometry: &str, scale: &str| {
+        let json = format!(
+            r#"{{"scene": {{"models": [
+                {{"geometry": {}, "material": {{"color": [255, 0, 0], "reflective": 0}}, "transform": [{{"scale": {}}}, {{"translate": [0, 0, 10]}}]}}
+            ]}}}}"#,
+            geometry, scale
+        );
+        Scene::from_reader(json.as_bytes())
+    };
+    let hit = |scene: &Scene, origin: Vec3<f64>| {
+        let ray = Ray::new(origin, Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+        scene.closest_intersection(&ray).map(|(_, i)| i.t)
+    };
+
+    let sphere = r#"{"type": "sphere", "center": [0, 0, 0], "radius": 1}"#;
+    let scene = load(sphere, "[2, 2, 2]").unwrap();
+    assert_eq!(Some(8.0), hit(&scene, Vec3::default()));
+    assert!(hit(&scene, Vec3::new(1.5, 0.0, 0.0)).is_some());
+
+    let err = load(sphere, "[1, 2, 1]").err().unwrap();
+    assert_eq!("model 0 can only be scaled uniformly", err.to_string());
+
+    // The cylinder around the z axis, starting at the translated origin.
+    let cylinder = r#"{"type": "cylinder", "base": [0, 0, 0], "axis": [0, 0, 1], "radius": 1, "height": 1}"#;
+    let scene = load(cylinder, "[3, 3, 3]").unwrap();
+    let ray = Ray::new(Vec3::new(-10.0, 0.0, 12.5), Vec3::new(1.0, 0.0, 0.0), 1.0e-6..1.0e20);
+    assert_eq!(Some(7.0), scene.closest_intersection(&ray).map(|(_, i)| i.t));
+
+    assert!(load(cylinder, "[3, 3, 1]").is_err());
+    assert!(load(r#"{"type": "plane", "point": [0, 0, 0], "normal": [0, 0, -1]}"#, "[1, 2, 1]").is_ok());
+}
+
+#[test]
+fn load_scene_transforms_volume() {
+    let json = r#"{"scene": {"models": [], "volumes": [
+        {"min": [-1, -1, -1], "max": [1, 1, 1], "density": 1, "albedo": 0, "transform": [{"translate": [0, 0, 5]}]}
+    ]}}"#;
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    assert_eq!(Some((4.0, 6.0)), scene.volumes[0].interval(&ray));
+}
+
+#[test]
+fn clip_planes_follow_model_transform() {
+    // The plane keeps the back of the sphere in model space, so rays from the
+    // origin see its inside, at the far side of the sphere.
+    let json = r#"{"scene": {"models": [
+        {"geometry": {"type": "sphere", "center": [0, 0, 0], "radius": 1}, "material": {"color": [255, 0, 0], "reflective": 0},
+         "clip": [{"point": [0, 0, 0.5], "normal": [0, 0, 1]}], "transform": [{"translate": [0, 0, 5]}]}
+    ]}}"#;
+    let mut scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+    let hit = |scene: &Scene| scene.closest_intersection(&ray).map(|(_, i)| i.t);
+
+    assert_eq!(Some(6.0), hit(&scene));
+
+    scene.set_model_transform(0, Matrix4x4::translation(Vec3::new(0.0, 0.0, 7.0)));
+    assert_eq!(Some(8.0), hit(&scene));
+}
+
+#[test]
+fn checker_pattern_alternates_between_cells() {
+    let json = r##"{"color": [0, 0, 0], "reflective": 0,
+        "pattern": {"type": "checker", "color_a": "#ffffff", "color_b": [255, 0, 0], "scale": 2.0}}"##;
+    let material: Material = serde_json::from_str(json).unwrap();
+
+    // Samples the floor `y = 0` seen from above.
+    let color = |x, z| {
+        let intersection = Intersection::new(1.0, Vec3::new(x, 0.0, z), Vec3::new(0.0, 1.0, 0.0));
+        material.color_at(&intersection)
+    };
+
+    assert_eq!(Rgb([255, 255, 255]), color(-1.0, 1.0));
+    assert_eq!(Rgb([255, 0, 0]), color(1.0, 1.0));
+    assert_eq!(Rgb([255, 0, 0]), color(-1.0, 3.0));
+    assert_eq!(Rgb([255, 255, 255]), color(1.0, 3.0));
+    assert_eq!(Rgb([255, 0, 0]), color(0.5, 1.9));
+}
+
+#[test]
+fn load_scene_ambient() {
+    let scene = |json: &str| Scene::from_reader(json.as_bytes()).unwrap();
+
+    assert_eq!(0.1, scene(r#"{"scene": {"models": []}}"#).ambient);
+    assert_eq!(0.3, scene(r#"{"scene": {"models": [], "ambient": 0.3}}"#).ambient);
+
+    // Scenes built in code are lit the same as loaded ones.
+    assert_eq!(0.1, Scene::new(Rgb([0, 0, 0])).ambient);
+    assert_eq!(0.1, SceneBuilder::new().build().unwrap().ambient);
+}
+
+#[test]
+fn load_scene_with_lights() {
+    let json = r#"{"scene": {"models": [], "lights": [
+        {"type": "point", "position": [1, 2, 3], "intensity": 0.5},
+        {"type": "point", "position": [-1, 0, 0], "intensity": 0.25}
+    ]}}"#;
+
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    assert_eq!(2, scene.lights.len());
+    assert_eq!(Vec3::new(1.0, 2.0, 3.0), scene.lights[0].pos());
+    assert_eq!(Vec3::new(-1.0, 0.0, 0.0), scene.lights[1].pos());
+}
+
+#[test]
+fn red_light_on_white_surface() {
+    let json = r##"{"scene": {"ambient": 0, "models": [
+        {"geometry": {"type": "sphere", "center": [0, 0, 5], "radius": 1}, "material": {"color": [255, 255, 255], "reflective": 0}}
+    ], "lights": [
+        {"type": "point", "position": [0, 0, 0], "intensity": 1, "color": "#ff0000"}
+    ]}}"##;
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    assert_eq!(Vec3::new(1.0, 0.0, 0.0), scene.lights[0].color());
+    assert_eq!(Rgb([255, 0, 0]), scene.trace(&ray));
+}
+
+#[test]
+fn refraction_through_sphere() {
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0);
+
+    let exit = |refractive_index| {
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+        let entry = sphere.intersection(&ray).unwrap();
+        let inside = refract(ray.direction(), &entry.normal.unit(), refractive_index).unwrap();
+
+        let ray = Ray::new(entry.point, inside, 1.0e-6..1.0e20);
+        let exit = sphere.intersection(&ray).unwrap();
+        refract(ray.direction(), &exit.normal.unit(), refractive_index).unwrap()
+    };
+
+    // Matching media don't bend rays at all.
+    let straight = exit(1.0);
+    assert!((straight - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-9);
+
+    // A denser sphere works as a converging lens, bending the ray towards the
+    // optical axis, and more so the denser it is.
+    let glass = exit(1.5);
+    let diamond = exit(2.4);
+    assert!((glass.len() - 1.0).abs() < 1e-9);
+    assert!(glass.y < 0.0);
+    assert!(diamond.y < glass.y);
+}
+
+#[test]
+fn refraction_total_internal_reflection() {
+    // Leaving glass at 60 degrees exceeds the critical angle of ~41.8 degrees.
+    let direction = Vec3::new(60f64.to_radians().sin(), 0.0, 60f64.to_radians().cos());
+    let normal = Vec3::new(0.0, 0.0, 1.0);
+
+    assert!(refract(&direction, &normal, 1.5).is_none());
+    assert!(refract(&direction.inverse(), &normal, 1.5).is_some());
+}
+
+#[test]
+fn trace_keeps_faint_reflections() {
+    // Two facing mirrors, which glow at a single 8-bit step. Every bounce adds
+    // half of the glow seen in the opposite mirror.
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 0.0;
+    scene.depth = 16;
+    for &(z, normal) in &[(0.0, 1.0), (1.0, -1.0)] {
+        scene.add_model(Model::new(
+            Box::new(Plane::new(Vec3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, normal))),
+            Material {
+                reflective: 0.5,
+                emissive: 1.0,
+                ..Material::new(Rgb([1, 1, 1]))
+            },
+        ));
+    }
+
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 0.5), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    let expected = (0..=16).map(|k| 0.5f64.powi(k)).sum::<f64>() / 255.0;
+    assert!((scene.trace_hdr(&ray).x - expected).abs() < 1e-9);
+    // Quantizing every bounce would round the halved glow down to nothing,
+    // leaving just the single step.
+    assert_eq!(Rgb([2, 2, 2]), scene.trace(&ray));
+}
+
+#[test]
+fn no_reflection_at_zero_depth() {
+    let mut scene = Scene::new(Rgb([10, 20, 30]));
+    scene.ambient = 0.0;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::default(),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material {
+            reflective: 1.0,
+            ..Material::new(Rgb([200, 0, 0]))
+        },
+    ));
+
+    let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    assert_eq!(Rgb([200, 0, 0]), scene.quantize(scene.trace_limited(&ray, 0)));
+    // A single bounce already sees only the background behind the camera.
+    assert_eq!(Rgb([10, 20, 30]), scene.quantize(scene.trace_limited(&ray, 1)));
+}
+
+#[test]
+fn schlick_reflectance() {
+    assert_eq!(0.04, schlick(1.0, 0.04));
+    assert_eq!(0.5, schlick(1.0, 0.5));
+    assert_eq!(1.0, schlick(0.0, 0.04));
+    assert!(schlick(0.01, 0.04) > 0.95);
+    assert!(schlick(0.5, 0.04) < schlick(0.2, 0.04));
+}
+
+#[test]
+fn bgra_to_image_swaps_channels() {
+    // Two pixels per row followed by two bytes of padding.
+    let buf = [
+        3, 2, 1, 0, 6, 5, 4, 0, 0xff, 0xff, //
+        9, 8, 7, 0, 12, 11, 10, 0, 0xff, 0xff,
+    ];
+
+    let image = bgra_to_image(&buf, 10, 2, 2);
+
+    assert_eq!(Rgb([1, 2, 3]), *image.get_pixel(0, 0));
+    assert_eq!(Rgb([4, 5, 6]), *image.get_pixel(1, 0));
+    assert_eq!(Rgb([7, 8, 9]), *image.get_pixel(0, 1));
+    assert_eq!(Rgb([10, 11, 12]), *image.get_pixel(1, 1));
+}
+
+#[test]
+fn load_malformed_scene() {
+    let error = |json: &str| match Scene::from_reader(json.as_bytes()) {
+        Ok(..) => panic!("scene must not load"),
+        Err(err) => err.to_string(),
+    };
+
+    assert_eq!("`scene` must be an object", error(r#"{"models": []}"#));
+    assert_eq!("`scene.models` must be an array", error(r#"{"scene": {"models": 42}}"#));
+    assert_eq!(
+        "model 1 has unknown geometry type `torus`",
+        error(r#"{"scene": {"models": [
+            {"geometry": {"type": "sphere", "center": [0, 0, 5], "radius": 1}, "material": {"color": [255, 0, 0], "reflective": 0}},
+            {"geometry": {"type": "torus"}, "material": {"color": [255, 0, 0], "reflective": 0}}
+        ]}}"#)
+    );
+    assert_eq!("model 0 missing `geometry.type`", error(r#"{"scene": {"models": [{"geometry": {}}]}}"#));
+    assert!(Scene::from_reader("{".as_bytes()).is_err());
+}
+
+#[test]
+fn point_light_ignores_normal_length() {
+    let light = PointLight {
+        intensity: 0.8,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(1.0, 1.0, -1.0),
+    };
+    let vertices = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)];
+    let n = Vec3::new(0.0, 0.0, -1.0);
+    let ray = Ray::new(Vec3::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    let unit = geometry::Triangle::new(vertices).with_normals([n, n, n]);
+    let scaled = geometry::Triangle::new(vertices).with_normals([n.scale(3.0), n.scale(0.5), n.scale(2.0)]);
+
+    let unit = light.intensity(&unit.intersection(&ray).unwrap());
+    let scaled = light.intensity(&scaled.intersection(&ray).unwrap());
+
+    assert!(unit > 0.0);
+    assert!((unit - scaled).abs() < 1e-12);
+}
+
+#[test]
+fn plane_lit_from_above_has_no_acne() {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 0.0;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 100.0, 0.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0))),
+        Material::new(Rgb([200, 200, 200])),
+    ));
+
+    let origin = Vec3::new(0.0, 1.0, 0.0);
+    for x in -20..20 {
+        for z in -20..20 {
+            let target = Vec3::new(x as f64 * 1.37, -1.0, z as f64 * 0.91);
+            let ray = Ray::new(origin, target - origin, 1.0e-6..1.0e20);
+            let (_, intersection) = scene.closest_intersection(&ray).unwrap();
+
+            let light = scene.lights[0].intensity(&intersection);
+            assert_eq!(Vec3::new(light, light, light), scene.lightning(&intersection, &ray.direction().inverse(), -1.0));
+        }
+    }
+}
+
+#[test]
+fn occluder_behind_light_casts_no_shadow() {
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 0.0;
+    scene.lights.push(Box::new(PointLight {
+        intensity: 1.0,
+        color: Rgb([255, 255, 255]),
+        position: Vec3::new(0.0, 0.0, 2.0),
+    }));
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0)),
+        Material::new(Rgb([200, 200, 200])),
+    ));
+    // Lies on the line from the lit point to the light, but beyond the light.
+    scene.add_model(Model::new(
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 0.5)),
+        Material::new(Rgb([0, 0, 0])),
+    ));
+
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20);
+
+    assert_eq!(Rgb([200, 200, 200]), scene.trace(&ray));
+}
+
+#[test]
+fn spot_light_cone() {
+    let light = SpotLight {
+        position: Vec3::default(),
+        direction: Vec3::new(0.0, 0.0, 1.0),
+        intensity: 0.5,
+        color: Rgb([255, 255, 255]),
+        inner_angle: 20.0,
+        outer_angle: 30.0,
+    };
+    // Point at the given angle from the axis, facing the light.
+    let at = |angle: f64| {
+        let point = Vec3::new(angle.to_radians().sin(), 0.0, angle.to_radians().cos()).scale(5.0);
+        light.intensity(&Intersection::new(5.0, point, -point))
+    };
+
+    assert_eq!(0.5, at(0.0));
+    assert!((at(19.0) - 0.5).abs() < 1e-12);
+    assert!(at(25.0) > 0.0 && at(25.0) < 0.5);
+    assert_eq!(0.0, at(31.0));
+}
+
+#[test]
+fn load_spot_light() {
+    let json = r#"{"scene": {"models": [], "lights": [{
+        "type": "spot", "position": [0, 5, 0], "direction": [0, -1, 0], "intensity": 1, "inner_angle": 10, "outer_angle": 15
+    }]}}"#;
+
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    assert_eq!(1, scene.lights.len());
+    assert_eq!(Vec3::new(0.0, 5.0, 0.0), scene.lights[0].pos());
+}
+
+#[test]
+fn area_light_penumbra() {
+    let scene = |occluded: bool| {
+        let mut scene = Scene::new(Rgb([0, 0, 0]));
+        scene.ambient = 0.0;
+        scene.lights.push(Box::new(AreaLight {
+            corner: Vec3::new(-1.0, 5.0, -1.0),
+            edges: (Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0)),
+            intensity: 1.0,
+            color: Rgb([255, 255, 255]),
+            samples: 64,
+        }));
+        if occluded {
+            // Covers the half of the light with negative x.
+            scene.add_model(Model::new(
+                Box::new(Sphere::new(Vec3::new(-50.0, 2.5, 0.0), 50.0)),
+                Material::new(Rgb([0, 0, 0])),
+            ));
+        }
+        scene
+    };
+    let intersection = Intersection::new(1.0, Vec3::default(), Vec3::new(0.0, 1.0, 0.0));
+    let view = Vec3::new(0.0, 1.0, 0.0);
+
+    let lit = scene(false).lightning(&intersection, &view, -1.0).x;
+    let penumbra = scene(true).lightning(&intersection, &view, -1.0).x;
+
+    assert_eq!(1.0, lit);
+    assert!(penumbra > 0.4 * lit && penumbra < 0.6 * lit, "{}", penumbra);
+}
+
+#[test]
+fn textured_material() {
+    use crate::{geometry::Triangle, vec2::Vec2};
+
+    let texture = Texture::new(ImageBuffer::from_fn(2, 1, |x, _| if x == 0 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) }));
+    let triangle = Triangle::new([Vec3::new(0.0, 0.0, 5.0), Vec3::new(4.0, 0.0, 5.0), Vec3::new(0.0, 4.0, 5.0)])
+        .with_uvs([Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0)]);
+
+    let mut scene = Scene::new(Rgb([0, 0, 0]));
+    scene.ambient = 1.0;
+    scene.add_model(Model::new(
+        Box::new(triangle),
+        Material {
+            texture: Some(Arc::new(texture)),
+            ..Material::new(Rgb([0, 255, 0]))
+        },
+    ));
+
+    let color = |x| scene.trace(&Ray::new(Vec3::new(x, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0e-6..1.0e20));
+
+    assert_eq!(Rgb([255, 0, 0]), color(0.5));
+    assert_eq!(Rgb([0, 0, 255]), color(3.0));
+}
+
+#[cfg(test)]
+fn load_sphere_center(transform: &str) -> Vec3<f64> {
+    let json = format!(
+        r#"{{"scene": {{"models": [{{
+            "geometry": {{"type": "sphere", "center": [0, 0, 0], "radius": 1}},
+            "material": {{"color": [255, 255, 255], "reflective": 0}},
+            "transform": {}
+        }}]}}}}"#,
+        transform
+    );
+    let scene = Scene::from_reader(json.as_bytes()).unwrap();
+
+    // Probe the sphere from both sides along the x axis to find its center.
+    let hit = |origin: f64, direction: f64| {
+        let ray = Ray::new(Vec3::new(origin, 0.0, 5.0), Vec3::new(direction, 0.0, 0.0), 1.0e-6..1.0e20);
+        scene.closest_intersection(&ray).unwrap().1.point
+    };
+    let left = hit(-100.0, 1.0);
+    let right = hit(100.0, -1.0);
+
+    (left + right).scale(0.5)
+}
+
+#[test]
+fn load_scene_with_transform_operations() {
+    let matrix = load_sphere_center("[[1, 0, 0, 2], [0, 1, 0, 0], [0, 0, 1, 5], [0, 0, 0, 1]]");
+    let ops = load_sphere_center(r#"[{"translate": [0, 0, 1]}, {"rotate_y": 90}, {"scale": [2, 2, 2]}, {"translate": [0, 0, 5]}]"#);
+
+    assert!(matrix.distance(&Vec3::new(2.0, 0.0, 5.0)) < 1e-9);
+    assert!(matrix.distance(&ops) < 1e-9);
+}
+
+#[test]
+fn load_scene_with_unknown_transform_operation() {
+    let json = r#"{"scene": {"models": [{
+        "geometry": {"type": "sphere", "center": [0, 0, 0], "radius": 1},
+        "material": {"color": [255, 255, 255], "reflective": 0},
+        "transform": [{"shear": 1}]
+    }]}}"#;
+
+    let err = Scene::from_reader(json.as_bytes()).err().unwrap();
+    assert_eq!("unknown transform operation `shear`", err.to_string());
 }
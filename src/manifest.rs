@@ -0,0 +1,131 @@
+//! Batch rendering of jobs listed in a manifest file.
+//!
+//! A manifest is a JSON file of the form:
+//!
+//! ```json
+//! {
+//!     "jobs": [
+//!         {
+//!             "scene": "scene.json",
+//!             "output": "front.png",
+//!             "width": 800,
+//!             "height": 600,
+//!             "origin": [0.0, 0.0, -2.0],
+//!             "depth": 4
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Both `origin` and `depth` are optional.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::{camera::Camera, headless, vec3::Vec3, Scene};
+
+fn default_origin() -> Vec3<f64> {
+    Vec3::new(0.0, 0.0, -2.0)
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Job {
+    scene: PathBuf,
+    output: PathBuf,
+    width: u32,
+    height: u32,
+    #[serde(default = "default_origin")]
+    origin: Vec3<f64>,
+    depth: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Manifest {
+    jobs: Vec<Job>,
+}
+
+impl Manifest {
+    pub fn load<P: AsRef<Path>>(path: &P) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let manifest = serde_json::from_reader(file)?;
+
+        Ok(manifest)
+    }
+
+    /// Renders all jobs one after another.
+    ///
+    /// Each scene is loaded once, even when several jobs refer to it.
+    pub fn run(&self) -> Result<(), Box<dyn Error>> {
+        let mut scenes = HashMap::new();
+
+        for job in &self.jobs {
+            let scene = match scenes.entry(&job.scene) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(Scene::load(&job.scene)?),
+            };
+
+            let depth = scene.depth;
+            if let Some(depth) = job.depth {
+                scene.depth = depth;
+            }
+
+            let camera = Camera {
+                position: job.origin,
+                target: job.origin + Vec3::new(0.0, 0.0, 1.0),
+                ..scene.camera
+            };
+            let result = headless::render_to_file(scene, &camera, job.width, job.height, &job.output);
+
+            scene.depth = depth;
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn run_two_jobs() {
+    let dir = std::env::temp_dir().join("photon-manifest-test");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let scene = dir.join("scene.json");
+    std::fs::write(
+        &scene,
+        r#"{"scene": {"models": [{"geometry": {"type": "sphere", "center": [0, 0, 5], "radius": 1}, "material": {"color": [255, 0, 0], "reflective": 0}}]}}"#,
+    )
+    .unwrap();
+
+    let job = |output: &str, width, height, depth| Job {
+        scene: scene.clone(),
+        output: dir.join(output),
+        width,
+        height,
+        origin: default_origin(),
+        depth,
+    };
+
+    let manifest = Manifest {
+        jobs: vec![job("a.png", 8, 4, None), job("b.png", 3, 5, Some(0))],
+    };
+
+    manifest.run().unwrap();
+
+    assert_eq!((8, 4), image::open(dir.join("a.png")).unwrap().to_rgb().dimensions());
+    assert_eq!((3, 5), image::open(dir.join("b.png")).unwrap().to_rgb().dimensions());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn deserialize_defaults() {
+    let manifest: Manifest =
+        serde_json::from_str(r#"{"jobs": [{"scene": "scene.json", "output": "out.png", "width": 4, "height": 2}]}"#).unwrap();
+
+    assert_eq!(default_origin(), manifest.jobs[0].origin);
+    assert_eq!(None, manifest.jobs[0].depth);
+}
@@ -11,12 +11,88 @@ impl<T: Copy> Matrix4x4<T> {
     pub fn new(v: [[T; 4]; 4]) -> Self {
         Matrix4x4([Vec4::from(v[0]), Vec4::from(v[1]), Vec4::from(v[2]), Vec4::from(v[3])])
     }
+
+    /// Swaps rows and columns, e.g. to convert column-major data.
+    pub fn transpose(&self) -> Self {
+        let mut m = *self;
+        for i in 0..4 {
+            for j in 0..4 {
+                m.0[i][j] = self.0[j][i];
+            }
+        }
+
+        m
+    }
 }
 
 impl Matrix4x4<f64> {
     pub fn identity() -> Self {
         Matrix4x4::new([[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]])
     }
+
+    pub fn translation(offset: Vec3<f64>) -> Self {
+        Matrix4x4::new([[1.0, 0.0, 0.0, offset.x], [0.0, 1.0, 0.0, offset.y], [0.0, 0.0, 1.0, offset.z], [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn scaling(factor: Vec3<f64>) -> Self {
+        Matrix4x4::new([[factor.x, 0.0, 0.0, 0.0], [0.0, factor.y, 0.0, 0.0], [0.0, 0.0, factor.z, 0.0], [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// View matrix of the camera at `eye` looking at `target`, transforming
+    /// world coordinates into the camera ones.
+    ///
+    /// The camera looks along the z axis, with x pointing to the right and y
+    /// up. The approximate `up` doesn't have to be orthogonal to the view
+    /// direction.
+    pub fn look_at(eye: Vec3<f64>, target: Vec3<f64>, up: Vec3<f64>) -> Self {
+        let w = (target - eye).unit();
+        let u = up.cross(&w).unit();
+        let v = w.cross(&u);
+
+        Matrix4x4::new([
+            [u.x, u.y, u.z, -u.dot(&eye)],
+            [v.x, v.y, v.z, -v.dot(&eye)],
+            [w.x, w.y, w.z, -w.dot(&eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Perspective projection of the camera space as set up by `look_at`, given
+    /// the vertical field of view in radians and the width to height ratio.
+    ///
+    /// Points become normalized device coordinates after the divide by `w`,
+    /// see `Vec4::homogenize`. As in OpenGL, the visible volume maps to
+    /// `[-1, 1]` along every axis, with the near plane at `z = -1` and the far
+    /// one at `z = 1`.
+    pub fn perspective(fov_y: f64, aspect: f64, near: f64, far: f64) -> Self {
+        let f = 1.0 / (fov_y / 2.0).tan();
+        let depth = far - near;
+
+        Matrix4x4::new([
+            [f / aspect, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, (far + near) / depth, -2.0 * far * near / depth],
+            [0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    /// Counter-clockwise rotation around the x axis by the given angle in radians.
+    pub fn rotation_x(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix4x4::new([[1.0, 0.0, 0.0, 0.0], [0.0, cos, -sin, 0.0], [0.0, sin, cos, 0.0], [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// Counter-clockwise rotation around the y axis by the given angle in radians.
+    pub fn rotation_y(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix4x4::new([[cos, 0.0, sin, 0.0], [0.0, 1.0, 0.0, 0.0], [-sin, 0.0, cos, 0.0], [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// Counter-clockwise rotation around the z axis by the given angle in radians.
+    pub fn rotation_z(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix4x4::new([[cos, -sin, 0.0, 0.0], [sin, cos, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]])
+    }
 }
 
 impl Matrix4x4<f64> {
@@ -69,11 +145,43 @@ impl Matrix4x4<f64> {
         Matrix4x4::new(m)
     }
 
-    pub fn transform_normal(n: &Vec3<f64>, inv: Matrix4x4<f64>) -> Vec3<f64> {
-        Vec3 {
-            x: inv.0[0][0] * n.x + inv.0[1][0] * n.y + inv.0[2][0] * n.z,
-            y: inv.0[0][1] * n.x + inv.0[1][1] * n.y + inv.0[2][1] * n.z,
-            z: inv.0[0][2] * n.x + inv.0[1][2] * n.y + inv.0[2][2] * n.z,
+    /// Returns the inverse-transpose, which transforms normals so that they
+    /// stay perpendicular to surfaces under non-uniform scaling and shear.
+    ///
+    /// Transformed normals are not unit length in general.
+    pub fn normal_matrix(&self) -> Self {
+        self.inverse().transpose()
+    }
+
+    /// Returns the lengths the x, y and z unit vectors are scaled to.
+    pub fn axis_scales(&self) -> Vec3<f64> {
+        let axis = |j: usize| Vec3::new(self.0[0][j], self.0[1][j], self.0[2][j]);
+
+        Vec3::new(axis(0).len(), axis(1).len(), axis(2).len())
+    }
+
+    /// Returns the factor lengths are scaled by, if they are scaled equally in
+    /// every direction, i.e. the matrix combines rotations, reflections and
+    /// translations with a uniform scaling only.
+    pub fn uniform_scale(&self) -> Option<f64> {
+        let axis = |j: usize| Vec3::new(self.0[0][j], self.0[1][j], self.0[2][j]);
+        let (x, y, z) = (axis(0), axis(1), axis(2));
+
+        let scale = x.len();
+        let tolerance = 1e-9 * scale * scale;
+        let equal = |a: f64, b: f64| (a - b).abs() <= tolerance;
+
+        let uniform = scale > 0.0
+            && equal(y.dot(&y), scale * scale)
+            && equal(z.dot(&z), scale * scale)
+            && equal(x.dot(&y), 0.0)
+            && equal(x.dot(&z), 0.0)
+            && equal(y.dot(&z), 0.0);
+
+        if uniform {
+            Some(scale)
+        } else {
+            None
         }
     }
 }
@@ -82,12 +190,7 @@ impl<'a, T: Copy + Add<Output = T> + Mul<Output = T>> Mul<Vec4<T>> for &'a Matri
     type Output = Vec4<T>;
 
     fn mul(self, vec: Vec4<T>) -> Self::Output {
-        Vec4::new(
-            vec[0] * self.0[0][0] + vec[1] * self.0[0][1] + vec[2] * self.0[0][2] + vec[3] * self.0[0][3],
-            vec[1] * self.0[1][0] + vec[1] * self.0[1][1] + vec[2] * self.0[1][2] + vec[3] * self.0[1][3],
-            vec[2] * self.0[2][0] + vec[1] * self.0[2][1] + vec[2] * self.0[2][2] + vec[3] * self.0[2][3],
-            vec[3] * self.0[3][0] + vec[1] * self.0[3][1] + vec[2] * self.0[3][2] + vec[3] * self.0[3][3],
-        )
+        Vec4::new(self.0[0].dot(&vec), self.0[1].dot(&vec), self.0[2].dot(&vec), self.0[3].dot(&vec))
     }
 }
 
@@ -100,8 +203,7 @@ impl Mul<Matrix4x4<f64>> for Matrix4x4<f64> {
         for i in 0..4 {
             for j in 0..4 {
                 for k in 0..4 {
-                    let mut v = out.0[i][j] + self.0[i][k] * o.0[k][j];
-                    out.0[i][j] = v;
+                    out.0[i][j] += self.0[i][k] * o.0[k][j];
                 }
             }
         }
@@ -118,8 +220,144 @@ fn mul_matrix_vec() {
     assert_eq!(Vec4::new(20, 10, 10, 1), matrix * vec);
 }
 
+#[test]
+fn mul_matrix_vec_rotation_translation() {
+    // Rotation by 90 degrees around the z axis followed by translation.
+    let matrix = &Matrix4x4::new([[0, -1, 0, 1], [1, 0, 0, 2], [0, 0, 1, 3], [0, 0, 0, 1]]);
+    let vec = Vec4::new(1, 2, 3, 1);
+
+    assert_eq!(Vec4::new(-1, 3, 6, 1), matrix * vec);
+}
+
 #[test]
 fn inverse_identity() {
     let i = Matrix4x4::identity();
     assert_eq!(i, i.inverse());
 }
+
+#[test]
+fn mul_matrix_matrix() {
+    let a = Matrix4x4::new([[1.0, 2.0, 3.0, 4.0], [5.0, 6.0, 7.0, 8.0], [9.0, 10.0, 11.0, 12.0], [13.0, 14.0, 15.0, 16.0]]);
+    let b = Matrix4x4::new([[2.0, 0.0, 1.0, 0.0], [0.0, 1.0, 0.0, 3.0], [1.0, 0.0, 0.0, 2.0], [0.0, 4.0, 1.0, 1.0]]);
+
+    let expected = Matrix4x4::new([
+        [5.0, 18.0, 5.0, 16.0],
+        [17.0, 38.0, 13.0, 40.0],
+        [29.0, 58.0, 21.0, 64.0],
+        [41.0, 78.0, 29.0, 88.0],
+    ]);
+    assert_eq!(expected, a * b);
+}
+
+#[test]
+fn mul_matrix_identity() {
+    let a = Matrix4x4::new([[1.0, 2.0, 3.0, 4.0], [5.0, 6.0, 7.0, 8.0], [9.0, 10.0, 11.0, 12.0], [13.0, 14.0, 15.0, 16.0]]);
+
+    assert_eq!(a, a * Matrix4x4::identity());
+    assert_eq!(a, Matrix4x4::identity() * a);
+}
+
+#[test]
+fn translation_moves_point() {
+    let matrix = Matrix4x4::translation(Vec3::new(1.0, 2.0, 3.0));
+
+    assert_eq!(Vec4::new(2.0, 3.0, 4.0, 1.0), &matrix * Vec4::new(1.0, 1.0, 1.0, 1.0));
+    // Directions are not affected by translation.
+    assert_eq!(Vec4::new(1.0, 1.0, 1.0, 0.0), &matrix * Vec4::new(1.0, 1.0, 1.0, 0.0));
+}
+
+#[test]
+fn scaling_scales_point() {
+    let matrix = Matrix4x4::scaling(Vec3::new(2.0, 3.0, 4.0));
+
+    assert_eq!(Vec4::new(2.0, 3.0, 4.0, 1.0), &matrix * Vec4::new(1.0, 1.0, 1.0, 1.0));
+}
+
+#[test]
+fn uniform_scale_of_rotated_scaling() {
+    let matrix = Matrix4x4::rotation_x(0.3) * Matrix4x4::scaling(Vec3::new(2.0, 2.0, 2.0)) * Matrix4x4::translation(Vec3::new(1.0, 0.0, 0.0));
+    assert!((matrix.uniform_scale().unwrap() - 2.0).abs() < 1e-12);
+
+    assert_eq!(None, Matrix4x4::scaling(Vec3::new(1.0, 2.0, 1.0)).uniform_scale());
+    assert_eq!(None, Matrix4x4::new([[0.0; 4]; 4]).uniform_scale());
+    assert_eq!(Vec3::new(1.0, 2.0, 3.0), Matrix4x4::scaling(Vec3::new(1.0, -2.0, 3.0)).axis_scales());
+}
+
+#[test]
+fn rotation_quarter_turn() {
+    use std::f64::consts::FRAC_PI_2;
+
+    let close = |a: Vec4<f64>, b: Vec4<f64>| (0..4).all(|i| (a[i] - b[i]).abs() < 1e-12);
+
+    let x = Vec4::new(1.0, 0.0, 0.0, 0.0);
+    let y = Vec4::new(0.0, 1.0, 0.0, 0.0);
+    let z = Vec4::new(0.0, 0.0, 1.0, 0.0);
+
+    assert!(close(y, &Matrix4x4::rotation_z(FRAC_PI_2) * x));
+    assert!(close(z, &Matrix4x4::rotation_x(FRAC_PI_2) * y));
+    assert!(close(x, &Matrix4x4::rotation_y(FRAC_PI_2) * z));
+}
+
+#[test]
+fn normal_matrix_of_rotation() {
+    let rotation = Matrix4x4::rotation_y(0.3) * Matrix4x4::rotation_x(1.2);
+    let normal = rotation.normal_matrix();
+
+    for i in 0..4 {
+        for j in 0..4 {
+            assert!((rotation.0[i][j] - normal.0[i][j]).abs() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn mul_matrix_vec_rows() {
+    let matrix = Matrix4x4::new([[1, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12], [13, 14, 15, 16]]);
+    let vec = Vec4::new(1, 0, -1, 2);
+
+    assert_eq!(Vec4::new(6, 14, 22, 30), &matrix * vec);
+}
+
+#[test]
+fn transpose_twice_is_identity() {
+    let m = Matrix4x4::new([[1, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12], [13, 14, 15, 16]]);
+
+    assert_eq!(Matrix4x4::new([[1, 5, 9, 13], [2, 6, 10, 14], [3, 7, 11, 15], [4, 8, 12, 16]]), m.transpose());
+    assert_eq!(m, m.transpose().transpose());
+    assert_eq!(Matrix4x4::identity(), Matrix4x4::identity().transpose());
+}
+
+#[test]
+fn look_at_basis() {
+    let eye = Vec3::new(1.0, 2.0, 3.0);
+    let target = Vec3::new(4.0, -2.0, 3.0);
+    let view = Matrix4x4::look_at(eye, target, Vec3::new(0.0, 1.0, 0.0));
+
+    let row = |i: usize| Vec3::new(view.0[i][0], view.0[i][1], view.0[i][2]);
+    assert_eq!(Vec3::new(0.6, -0.8, 0.0), row(2));
+    for i in 0..3 {
+        assert!((row(i).len() - 1.0).abs() < 1e-12);
+        assert!(row(i).dot(&row((i + 1) % 3)).abs() < 1e-12);
+    }
+
+    let origin: Vec3<f64> = (&view * Vec4::from(eye)).into();
+    let forward: Vec3<f64> = (&view * Vec4::from(target)).into();
+    assert!(origin.len() < 1e-12);
+    assert!(forward.distance(&Vec3::new(0.0, 0.0, 5.0)) < 1e-12);
+}
+
+#[test]
+fn perspective_maps_frustum_to_ndc() {
+    let projection = Matrix4x4::perspective(std::f64::consts::FRAC_PI_2, 2.0, 1.0, 100.0);
+    let project = |x, y, z| (&projection * Vec4::new(x, y, z, 1.0)).homogenize();
+
+    assert!((project(0.0, 0.0, 1.0).z + 1.0).abs() < 1e-12);
+    assert!((project(0.0, 0.0, 100.0).z - 1.0).abs() < 1e-12);
+
+    // The corner of the frustum at the distance of 10, with 90 degrees field
+    // of view and twice as wide as high.
+    let corner = project(20.0, 10.0, 10.0);
+    assert!((corner.x - 1.0).abs() < 1e-12);
+    assert!((corner.y - 1.0).abs() < 1e-12);
+    assert!(corner.z > -1.0 && corner.z < 1.0);
+}
@@ -34,6 +34,13 @@ impl Ray<f64> {
     }
 }
 
+impl<T: Copy> Ray<T> {
+    #[inline]
+    pub fn range(&self) -> Range<T> {
+        self.range.start..self.range.end
+    }
+}
+
 impl<T: PartialOrd> Ray<T> {
     #[inline]
     pub fn contains(&self, t: T) -> bool {
@@ -44,6 +51,18 @@ impl<T: PartialOrd> Ray<T> {
 impl Transform<f64> for Ray<f64> {
     fn transform(&mut self, transformation: &Matrix4x4<f64>) {
         self.origin = (transformation * Vec4::from(self.origin)).into();
-        self.direction = (transformation * Vec4::from(self.direction)).into();
+        let direction: Vec3<f64> = (transformation * Vec4::direction(self.direction)).into();
+        self.direction = direction.unit();
     }
 }
+
+#[test]
+fn transform_keeps_direction_unit() {
+    let mut ray = Ray::new(Vec3::default(), Vec3::new(1.0, 0.0, 0.0), 0.0..1.0e20);
+    let transformation = Matrix4x4::translation(Vec3::new(1.0, 2.0, 3.0)) * Matrix4x4::rotation_z(std::f64::consts::FRAC_PI_2);
+    ray.transform(&transformation);
+
+    assert_eq!(Vec3::new(1.0, 2.0, 3.0), ray.origin());
+    assert!((ray.direction().len() - 1.0).abs() < 1e-12);
+    assert!(ray.direction().distance(&Vec3::new(0.0, 1.0, 0.0)) < 1e-12);
+}
@@ -0,0 +1,196 @@
+//! Sampling routines for stochastic integration.
+
+use std::f64;
+
+use crate::{vec2::Vec2, vec3::Vec3};
+
+/// Maps two uniform random numbers in `[0, 1)` to a direction on the
+/// hemisphere around the given normal with cosine-weighted density.
+pub fn cosine_sample_hemisphere(normal: &Vec3<f64>, u: f64, v: f64) -> Vec3<f64> {
+    let w = normal.unit();
+
+    // Build an orthonormal basis around the normal, using whichever axis is
+    // far enough from it to produce a stable cross product.
+    let helper = if w.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t = helper.cross(&w).unit();
+    let b = w.cross(&t);
+
+    let r = u.sqrt();
+    let phi = 2.0 * f64::consts::PI * v;
+
+    t.scale(r * phi.cos()) + b.scale(r * phi.sin()) + w.scale((1.0 - u).max(0.0).sqrt())
+}
+
+/// Maps two uniform random numbers in `[0, 1)` to a point of the unit disk
+/// with uniform density.
+pub fn uniform_sample_disk(u: f64, v: f64) -> Vec2<f64> {
+    let r = u.sqrt();
+    let phi = 2.0 * f64::consts::PI * v;
+
+    Vec2::new(r * phi.cos(), r * phi.sin())
+}
+
+/// Returns the `index`-th element of the van der Corput sequence in the given
+/// base.
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f64;
+
+    while index > 0 {
+        result += (index % base) as f64 * fraction;
+        index /= base;
+        fraction /= base as f64;
+    }
+
+    result
+}
+
+/// Returns the `index`-th point of the 2-3 Halton sequence, which evenly
+/// covers the unit square.
+pub fn halton(index: u64) -> (f64, f64) {
+    (radical_inverse(index, 2), radical_inverse(index, 3))
+}
+
+/// Hashes the value into uniformly distributed bits, see SplitMix64.
+fn hash(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns a pseudo-random point of the unit square determined by the seed and
+/// the index, e.g. of a pixel.
+///
+/// The same arguments always give the same point, so that renders are
+/// reproducible regardless of the order threads trace pixels in.
+pub fn scramble(seed: u64, index: u64) -> Vec2<f64> {
+    let bits = hash(seed ^ hash(index));
+    let unit = |bits: u64| (bits >> 11) as f64 / (1u64 << 53) as f64;
+
+    Vec2::new(unit(bits & 0xffff_ffff_0000_0000), unit(bits << 32))
+}
+
+/// Shifts the sample by the given rotation, wrapping it around the unit
+/// square.
+///
+/// Every pixel shifting its samples differently turns aliasing patterns of
+/// the shared sequence into noise, while keeping the samples evenly spread.
+pub fn rotate(sample: Vec2<f64>, rotation: Vec2<f64>) -> Vec2<f64> {
+    let wrap = |c: f64| c - c.floor();
+
+    Vec2::new(wrap(sample.x + rotation.x), wrap(sample.y + rotation.y))
+}
+
+/// Returns the sub-pixel sample offset in `[0, 1)` for the given frame.
+///
+/// Offsets follow the 2-3 Halton sequence, so frames accumulated over time
+/// evenly cover the pixel footprint.
+pub fn subpixel_offset(frame: u64) -> Vec2<f64> {
+    // The sequence starts at one, because the zeroth element is the origin for
+    // every base.
+    let (dx, dy) = halton(frame + 1);
+    Vec2::new(dx, dy)
+}
+
+/// Returns the lens sample in `[0, 1)` for the given frame.
+///
+/// Samples follow the 5-7 Halton sequence, so that they don't correlate with
+/// the sub-pixel offsets.
+pub fn lens_offset(frame: u64) -> Vec2<f64> {
+    Vec2::new(radical_inverse(frame + 1, 5), radical_inverse(frame + 1, 7))
+}
+
+#[cfg(test)]
+fn stratified(n: usize) -> Vec<(f64, f64)> {
+    let mut samples = Vec::with_capacity(n * n);
+    for i in 0..n {
+        for j in 0..n {
+            samples.push(((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64));
+        }
+    }
+
+    samples
+}
+
+#[test]
+fn cosine_samples_lie_in_hemisphere() {
+    let normals = [Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, -2.0, 3.0)];
+
+    for normal in &normals {
+        for (u, v) in stratified(32) {
+            let direction = cosine_sample_hemisphere(normal, u, v);
+
+            assert!(direction.dot(normal) >= 0.0);
+            assert!((direction.len() - 1.0).abs() < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn cosine_samples_mean_aligns_with_normal() {
+    let normal = Vec3::new(1.0, -2.0, 3.0);
+
+    let mean = stratified(32)
+        .into_iter()
+        .fold(Vec3::default(), |sum, (u, v)| sum + cosine_sample_hemisphere(&normal, u, v));
+
+    assert!(mean.unit().dot(&normal.unit()) > 0.999);
+}
+
+#[test]
+fn disk_samples_lie_in_unit_disk() {
+    for (u, v) in stratified(32) {
+        let point = uniform_sample_disk(u, v);
+
+        assert!(point.dot(&point) <= 1.0);
+    }
+}
+
+#[test]
+fn scramble_is_deterministic() {
+    assert_eq!(scramble(1, 2), scramble(1, 2));
+    assert_ne!(scramble(1, 2), scramble(2, 2));
+    assert_ne!(scramble(1, 2), scramble(1, 3));
+
+    for index in 0..1024 {
+        let point = scramble(42, index);
+        assert!(point.x >= 0.0 && point.x < 1.0);
+        assert!(point.y >= 0.0 && point.y < 1.0);
+    }
+}
+
+#[test]
+fn rotate_wraps_around() {
+    assert_eq!(Vec2::new(0.25, 0.0), rotate(Vec2::new(0.75, 0.5), Vec2::new(0.5, 0.5)));
+}
+
+#[test]
+fn consecutive_frames_use_different_offsets() {
+    for frame in 0..64 {
+        assert_ne!(subpixel_offset(frame), subpixel_offset(frame + 1));
+    }
+}
+
+#[test]
+fn subpixel_offsets_cover_pixel_uniformly() {
+    let mut cells = [[0; 4]; 4];
+    for frame in 0..1024 {
+        let offset = subpixel_offset(frame);
+
+        assert!(offset.x >= 0.0 && offset.x < 1.0);
+        assert!(offset.y >= 0.0 && offset.y < 1.0);
+
+        cells[(offset.x * 4.0) as usize][(offset.y * 4.0) as usize] += 1;
+    }
+
+    for row in &cells {
+        for &count in row {
+            assert!((count as i32 - 64).abs() <= 2, "{:?}", cells);
+        }
+    }
+}
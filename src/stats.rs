@@ -0,0 +1,88 @@
+//! Frame time statistics of the interactive renderer.
+
+use std::{fmt, time::Duration};
+
+/// Accumulates the time spent on frames and the number of rays traced in them.
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    frames: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    rays: u64,
+}
+
+impl RenderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, elapsed: Duration, rays: u64) {
+        if self.frames == 0 || elapsed < self.min {
+            self.min = elapsed;
+        }
+        if elapsed > self.max {
+            self.max = elapsed;
+        }
+
+        self.frames += 1;
+        self.total += elapsed;
+        self.rays += rays;
+    }
+
+    #[inline]
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    #[inline]
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns the mean frame time, zero when nothing is recorded.
+    pub fn mean(&self) -> Duration {
+        if self.frames == 0 {
+            Duration::default()
+        } else {
+            self.total / self.frames
+        }
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs() as f64 * 1e3 + duration.subsec_nanos() as f64 / 1e6;
+
+        write!(
+            fmt,
+            "{} frames: min {:.3} ms, mean {:.3} ms, max {:.3} ms, {} rays",
+            self.frames,
+            ms(self.min),
+            ms(self.mean()),
+            ms(self.max),
+            self.rays
+        )
+    }
+}
+
+#[test]
+fn stats_over_recorded_frames() {
+    let mut stats = RenderStats::new();
+    assert_eq!(Duration::default(), stats.mean());
+
+    for &(ms, rays) in &[(30, 100), (10, 200), (20, 300)] {
+        stats.record(Duration::from_millis(ms), rays);
+    }
+
+    assert_eq!(3, stats.frames());
+    assert_eq!(Duration::from_millis(10), stats.min());
+    assert_eq!(Duration::from_millis(20), stats.mean());
+    assert_eq!(Duration::from_millis(30), stats.max());
+    assert_eq!("3 frames: min 10.000 ms, mean 20.000 ms, max 30.000 ms, 600 rays", stats.to_string());
+}
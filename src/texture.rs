@@ -0,0 +1,86 @@
+//! Image textures mapped onto surfaces by texture coordinates.
+
+use std::{error::Error, path::Path};
+
+use image::{ImageBuffer, Rgb};
+
+use crate::vec2::Vec2;
+
+#[derive(Clone, Debug)]
+pub struct Texture {
+    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+}
+
+impl Texture {
+    pub fn new(image: ImageBuffer<Rgb<u8>, Vec<u8>>) -> Self {
+        Self { image }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        let image = image::open(path)?.to_rgb();
+
+        Ok(Self::new(image))
+    }
+
+    /// Decodes a texture from a `data:` URI holding a base64 encoded image.
+    pub fn from_data_uri(uri: &str) -> Result<Self, Box<Error>> {
+        let data = match uri.find(',') {
+            Some(pos) if uri.starts_with("data:") && uri[..pos].ends_with(";base64") => &uri[pos + 1..],
+            Some(..) | None => return Err("only base64 encoded `data:` URIs are supported".into()),
+        };
+
+        let bytes = base64::decode(data)?;
+        let image = image::load_from_memory(&bytes)?.to_rgb();
+
+        Ok(Self::new(image))
+    }
+
+    /// Returns the color of the texel at the given texture coordinates.
+    ///
+    /// Coordinates outside of `[0, 1)` wrap around, and `v` grows upwards,
+    /// i.e. `(0, 0)` is the bottom left corner of the image.
+    pub fn sample(&self, uv: &Vec2<f64>) -> Rgb<u8> {
+        let (width, height) = self.image.dimensions();
+
+        let u = uv.x - uv.x.floor();
+        let v = 1.0 - (uv.y - uv.y.floor());
+
+        let x = ((u * width as f64) as u32).min(width - 1);
+        let y = ((v * height as f64) as u32).min(height - 1);
+
+        *self.image.get_pixel(x, y)
+    }
+}
+
+#[cfg(test)]
+fn checker() -> Texture {
+    Texture::new(ImageBuffer::from_fn(3, 3, |x, y| {
+        if (x + y) % 2 == 0 {
+            Rgb([255, 255, 255])
+        } else {
+            Rgb([0, 0, 0])
+        }
+    }))
+}
+
+#[test]
+fn sample_corners_and_center() {
+    let texture = checker();
+    let white = Rgb([255, 255, 255]);
+    let black = Rgb([0, 0, 0]);
+
+    assert_eq!(white, texture.sample(&Vec2::new(0.1, 0.1)));
+    assert_eq!(white, texture.sample(&Vec2::new(0.9, 0.1)));
+    assert_eq!(white, texture.sample(&Vec2::new(0.1, 0.9)));
+    assert_eq!(white, texture.sample(&Vec2::new(0.9, 0.9)));
+    assert_eq!(white, texture.sample(&Vec2::new(0.5, 0.5)));
+    assert_eq!(black, texture.sample(&Vec2::new(0.5, 0.1)));
+}
+
+#[test]
+fn sample_wraps_around() {
+    let texture = checker();
+
+    assert_eq!(texture.sample(&Vec2::new(0.5, 0.1)), texture.sample(&Vec2::new(1.5, -0.9)));
+    assert_eq!(texture.sample(&Vec2::new(0.0, 0.0)), texture.sample(&Vec2::new(1.0, 1.0)));
+}
@@ -0,0 +1,83 @@
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Vec2<T> {
+    pub x: T,
+    pub y: T,
+}
+
+impl<T> Vec2<T> {
+    #[inline]
+    pub fn new(x: T, y: T) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T: Copy + Mul<Output = T>> Vec2<T> {
+    #[inline]
+    pub fn scale(&self, factor: T) -> Vec2<T> {
+        Vec2 {
+            x: self.x * factor,
+            y: self.y * factor,
+        }
+    }
+}
+
+impl<T: Copy + Add<Output = T> + Mul<Output = T>> Vec2<T> {
+    #[inline]
+    pub fn dot(&self, other: &Vec2<T>) -> T {
+        self.x * other.x + self.y * other.y
+    }
+}
+
+impl Vec2<f64> {
+    /// Interpolates per-vertex values of a triangle at the point with the
+    /// given barycentric coordinates.
+    #[inline]
+    pub fn barycentric(values: &[Vec2<f64>; 3], alpha: f64, beta: f64, gamma: f64) -> Self {
+        values[0].scale(alpha) + values[1].scale(beta) + values[2].scale(gamma)
+    }
+}
+
+impl<T: Add<Output = T>> Add for Vec2<T> {
+    type Output = Vec2<T>;
+
+    #[inline]
+    fn add(self, other: Vec2<T>) -> Self::Output {
+        Vec2 {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Vec2<T> {
+    type Output = Vec2<T>;
+
+    #[inline]
+    fn sub(self, other: Vec2<T>) -> Self::Output {
+        Vec2 {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+#[test]
+fn arithmetic() {
+    let a = Vec2::new(1.0, 2.0);
+    let b = Vec2::new(3.0, -4.0);
+
+    assert_eq!(Vec2::new(4.0, -2.0), a + b);
+    assert_eq!(Vec2::new(-2.0, 6.0), a - b);
+    assert_eq!(Vec2::new(2.0, 4.0), a.scale(2.0));
+    assert_eq!(-5.0, a.dot(&b));
+}
+
+#[test]
+fn barycentric() {
+    let values = [Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0)];
+
+    assert_eq!(values[1], Vec2::barycentric(&values, 0.0, 1.0, 0.0));
+    assert_eq!(Vec2::new(0.25, 0.5), Vec2::barycentric(&values, 0.25, 0.25, 0.5));
+}
@@ -1,12 +1,29 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Mul, Neg, Sub};
 
-#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Three component vector, serialized as an `[x, y, z]` array.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vec3<T> {
     pub x: T,
     pub y: T,
     pub z: T,
 }
 
+impl<T: Serialize> Serialize for Vec3<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.x, &self.y, &self.z).serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Vec3<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (x, y, z) = Deserialize::deserialize(deserializer)?;
+
+        Ok(Vec3::new(x, y, z))
+    }
+}
+
 impl<T> Vec3<T> {
     #[inline]
     pub fn new(x: T, y: T, z: T) -> Self {
@@ -33,6 +50,7 @@ impl<T: Copy + Add<Output = T> + Mul<Output = T>> Vec3<T> {
 }
 
 impl<T: Copy + Sub<Output = T> + Mul<Output = T>> Vec3<T> {
+    /// Right-handed cross product, i.e. `x.cross(y) == z`.
     #[inline]
     pub fn cross(&self, other: &Vec3<T>) -> Vec3<T> {
         Vec3 {
@@ -60,6 +78,31 @@ impl Vec3<f64> {
         }
     }
 
+    #[inline]
+    pub fn distance(&self, other: &Vec3<f64>) -> f64 {
+        self.distance_squared(other).sqrt()
+    }
+
+    /// Squared distance, cheaper than `distance` when only comparing.
+    #[inline]
+    pub fn distance_squared(&self, other: &Vec3<f64>) -> f64 {
+        let d = *self - *other;
+        d.dot(&d)
+    }
+
+    /// Linearly interpolates between two vectors, `t = 0.0` giving `self`.
+    #[inline]
+    pub fn lerp(&self, other: &Vec3<f64>, t: f64) -> Vec3<f64> {
+        self.scale(1.0 - t) + other.scale(t)
+    }
+
+    /// Reflects the incoming direction about the surface with the given unit
+    /// normal.
+    #[inline]
+    pub fn reflect(&self, normal: &Vec3<f64>) -> Vec3<f64> {
+        *self - normal.scale(2.0 * self.dot(normal))
+    }
+
     #[inline]
     pub fn inverse(&self) -> Vec3<f64> {
         Vec3 {
@@ -95,3 +138,89 @@ impl<T: Sub<Output = T>> Sub for Vec3<T> {
         }
     }
 }
+
+impl Mul<f64> for Vec3<f64> {
+    type Output = Vec3<f64>;
+
+    #[inline]
+    fn mul(self, factor: f64) -> Self::Output {
+        self.scale(factor)
+    }
+}
+
+impl Neg for Vec3<f64> {
+    type Output = Vec3<f64>;
+
+    #[inline]
+    fn neg(self) -> Self::Output {
+        self.inverse()
+    }
+}
+
+#[test]
+fn public_components() {
+    let mut v = Vec3::new(1.5, -2.0, 3.0);
+    v.z = 4.0;
+
+    assert_eq!((1.5, -2.0, 4.0), (v.x, v.y, v.z));
+    assert_eq!(Vec3 { x: 1.5, y: -2.0, z: 4.0 }, v);
+}
+
+#[test]
+fn cross_x_y() {
+    let x = Vec3::new(1.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 1.0, 0.0);
+
+    assert_eq!(Vec3::new(0.0, 0.0, 1.0), x.cross(&y));
+    assert_eq!(Vec3::new(0.0, 0.0, -1.0), y.cross(&x));
+}
+
+#[test]
+fn lerp() {
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    let b = Vec3::new(3.0, -2.0, 7.0);
+
+    assert_eq!(a, a.lerp(&b, 0.0));
+    assert_eq!(b, a.lerp(&b, 1.0));
+    assert_eq!(Vec3::new(2.0, 0.0, 5.0), a.lerp(&b, 0.5));
+}
+
+#[test]
+fn operators_match_methods() {
+    let v = Vec3::new(1.0, -2.0, 3.5);
+
+    assert_eq!(v.scale(2.0), v * 2.0);
+    assert_eq!(v.inverse(), -v);
+    assert_eq!(Vec3::new(-2.0, 4.0, -7.0), -v * 2.0);
+}
+
+#[test]
+fn reflect() {
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+
+    assert_eq!(Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0).reflect(&normal));
+    assert_eq!(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0).reflect(&normal));
+    // The side the normal points to doesn't matter.
+    assert_eq!(Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0).reflect(&-normal));
+}
+
+#[test]
+fn distance() {
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    let b = Vec3::new(2.0, 3.0, 4.0);
+
+    assert_eq!(3.0, a.distance_squared(&b));
+    assert_eq!(3f64.sqrt(), a.distance(&b));
+    assert_eq!(0.0, a.distance(&a));
+}
+
+#[test]
+fn serde_round_trip() {
+    let vec = Vec3::new(1.5, -2.0, 3.0);
+
+    let json = serde_json::to_string(&vec).unwrap();
+    assert_eq!("[1.5,-2.0,3.0]", json);
+    assert_eq!(vec, serde_json::from_str::<Vec3<f64>>(&json).unwrap());
+
+    assert!(serde_json::from_str::<Vec3<f64>>("[1.0, 2.0]").is_err());
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, Index, IndexMut};
+use std::ops::{Add, Index, IndexMut, Mul, Sub};
 
 use crate::vec3::Vec3;
 
@@ -41,6 +41,27 @@ impl<T: Copy> From<[T; 4]> for Vec4<T> {
     }
 }
 
+impl Vec4<f64> {
+    /// Converts a direction, i.e. a vector that translations leave unchanged.
+    ///
+    /// Unlike `From<Vec3<f64>>`, which treats the vector as a point, this sets
+    /// `w` to zero.
+    #[inline]
+    pub fn direction(v: Vec3<f64>) -> Self {
+        Vec4::new(v.x, v.y, v.z, 0.0)
+    }
+
+    /// Performs the perspective divide, i.e. divides `x`, `y` and `z` by `w`.
+    ///
+    /// Directions, which have zero `w`, are returned unchanged.
+    #[inline]
+    pub fn homogenize(&self) -> Vec3<f64> {
+        let w = if self.w() == 0.0 { 1.0 } else { self.w() };
+
+        Vec3::new(self.x() / w, self.y() / w, self.z() / w)
+    }
+}
+
 impl From<Vec3<f64>> for Vec4<f64> {
     #[inline]
     fn from(v: Vec3<f64>) -> Self {
@@ -48,6 +69,8 @@ impl From<Vec3<f64>> for Vec4<f64> {
     }
 }
 
+/// Drops `w`, which is right for points and directions transformed by affine
+/// matrices only. Use `homogenize` for projective ones.
 impl Into<Vec3<f64>> for Vec4<f64> {
     fn into(self) -> Vec3<f64> {
         Vec3::new(self.x(), self.y(), self.z())
@@ -61,6 +84,13 @@ impl<T: Copy + Add<Output = T> + Default> Vec4<T> {
     }
 }
 
+impl<T: Copy + Add<Output = T> + Mul<Output = T>> Vec4<T> {
+    #[inline]
+    pub fn dot(&self, other: &Vec4<T>) -> T {
+        self[0] * other[0] + self[1] * other[1] + self[2] * other[2] + self[3] * other[3]
+    }
+}
+
 impl<T> Index<usize> for Vec4<T> {
     type Output = T;
 
@@ -76,3 +106,69 @@ impl<T> IndexMut<usize> for Vec4<T> {
         &mut self.0[index]
     }
 }
+
+impl<T: Copy + Add<Output = T>> Add for Vec4<T> {
+    type Output = Vec4<T>;
+
+    #[inline]
+    fn add(self, other: Vec4<T>) -> Self::Output {
+        Vec4::new(self[0] + other[0], self[1] + other[1], self[2] + other[2], self[3] + other[3])
+    }
+}
+
+impl<T: Copy + Sub<Output = T>> Sub for Vec4<T> {
+    type Output = Vec4<T>;
+
+    #[inline]
+    fn sub(self, other: Vec4<T>) -> Self::Output {
+        Vec4::new(self[0] - other[0], self[1] - other[1], self[2] - other[2], self[3] - other[3])
+    }
+}
+
+impl<T: Copy + Mul<Output = T>> Mul<T> for Vec4<T> {
+    type Output = Vec4<T>;
+
+    #[inline]
+    fn mul(self, factor: T) -> Self::Output {
+        Vec4::new(self[0] * factor, self[1] * factor, self[2] * factor, self[3] * factor)
+    }
+}
+
+#[test]
+fn arithmetic() {
+    let a = Vec4::new(1, 2, 3, 4);
+    let b = Vec4::new(5, -6, 7, 0);
+
+    assert_eq!(Vec4::new(6, -4, 10, 4), a + b);
+    assert_eq!(Vec4::new(-4, 8, -4, 4), a - b);
+    assert_eq!(Vec4::new(2, 4, 6, 8), a * 2);
+}
+
+#[test]
+fn dot() {
+    let a = Vec4::new(1, 2, 3, 4);
+    let b = Vec4::new(5, -6, 7, 0);
+
+    assert_eq!(14, a.dot(&b));
+    assert_eq!(30, a.dot(&a));
+}
+
+#[test]
+fn translate_point_and_direction() {
+    use crate::matrix::Matrix4x4;
+
+    let translation = Matrix4x4::translation(Vec3::new(1.0, 2.0, 3.0));
+    let v = Vec3::new(1.0, 0.0, 0.0);
+
+    let point: Vec3<f64> = (&translation * Vec4::from(v)).into();
+    let direction: Vec3<f64> = (&translation * Vec4::direction(v)).into();
+
+    assert_eq!(Vec3::new(2.0, 2.0, 3.0), point);
+    assert_eq!(v, direction);
+}
+
+#[test]
+fn homogenize() {
+    assert_eq!(Vec3::new(1.0, 2.0, 3.0), Vec4::new(2.0, 4.0, 6.0, 2.0).homogenize());
+    assert_eq!(Vec3::new(2.0, 4.0, 6.0), Vec4::new(2.0, 4.0, 6.0, 0.0).homogenize());
+}